@@ -1,5 +1,6 @@
 use std::fs;
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
@@ -17,8 +18,38 @@ pub struct Config {
     pub allow_single_player: bool,
     pub firewall_damage: u32,
     pub damage_multiplier: f32,
+    #[serde(default = "default_tick_duration_ms")]
+    pub tick_duration_ms: u64,
+    /// Number of ticks of damage history sent to agents when they die.
+    #[serde(default = "default_death_report_ticks")]
+    pub death_report_ticks: u32,
+    /// Number of ticks the game state shown to spectating dead agents lags behind.
+    #[serde(default)]
+    pub spectator_delay_ticks: u32,
+    /// Number of ticks between full game states sent to dashboards receiving deltas.
+    #[serde(default = "default_keyframe_interval_ticks")]
+    pub keyframe_interval_ticks: u32,
+    /// Seconds between the pings sent to every client.
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// Seconds without receiving anything from a client before it is disconnected.
+    #[serde(default = "default_heartbeat_timeout_secs")]
+    pub heartbeat_timeout_secs: u64,
+    /// Number of ticks between two writes of the save file during a match, 0 disables autosaves.
+    #[serde(default = "default_autosave_interval_ticks")]
+    pub autosave_interval_ticks: u32,
     pub score: ScoreConfig,
     pub world_gen: WorldGenConfig,
+    #[serde(default)]
+    pub team_names: TeamNameConfig,
+    #[serde(default)]
+    pub outbound: OutboundConfig,
+    #[serde(default)]
+    pub duplicate_agent_policy: DuplicateAgentPolicy,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub lobby: LobbyConfig,
 }
 
 impl Default for Config {
@@ -31,8 +62,20 @@ impl Default for Config {
             allow_single_player: false,
             firewall_damage: 10,
             damage_multiplier: 1.5,
+            tick_duration_ms: default_tick_duration_ms(),
+            death_report_ticks: default_death_report_ticks(),
+            spectator_delay_ticks: 0,
+            keyframe_interval_ticks: default_keyframe_interval_ticks(),
+            heartbeat_interval_secs: default_heartbeat_interval_secs(),
+            heartbeat_timeout_secs: default_heartbeat_timeout_secs(),
+            autosave_interval_ticks: default_autosave_interval_ticks(),
             score: ScoreConfig::default(),
             world_gen: WorldGenConfig::default(),
+            team_names: TeamNameConfig::default(),
+            outbound: OutboundConfig::default(),
+            duplicate_agent_policy: DuplicateAgentPolicy::default(),
+            limits: LimitsConfig::default(),
+            lobby: LobbyConfig::default(),
         }
     }
 }
@@ -61,7 +104,31 @@ impl Config {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+fn default_tick_duration_ms() -> u64 {
+    500
+}
+
+fn default_death_report_ticks() -> u32 {
+    10
+}
+
+fn default_keyframe_interval_ticks() -> u32 {
+    20
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    10
+}
+
+fn default_heartbeat_timeout_secs() -> u64 {
+    30
+}
+
+fn default_autosave_interval_ticks() -> u32 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScoreConfig {
     pub victory: i32,
     pub kill_player: i32,
@@ -99,6 +166,13 @@ pub struct WorldGenConfig {
     pub width: u32,
     pub height: u32,
     pub seed: Option<u64>,
+    /// Share the seed of each match with the agents in the game start message.
+    #[serde(default)]
+    pub public_seed: bool,
+    /// Hand-authored map files used in turn by successive matches instead of generating the
+    /// terrain, see `MapFile` for their format.
+    #[serde(default)]
+    pub maps: Vec<PathBuf>,
 
     pub firewall_pattern: FirewallPattern,
     pub firewall_speed: u32,
@@ -121,6 +195,8 @@ impl Default for WorldGenConfig {
             width: 200,
             height: 200,
             seed: None,
+            public_seed: false,
+            maps: Vec::new(),
             firewall_pattern: FirewallPattern::FourCorner,
             firewall_speed: 8,
             chest_max_number: 1000,
@@ -136,3 +212,136 @@ impl Default for WorldGenConfig {
         }
     }
 }
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TeamNameConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub allowed_characters: Vec<NameCharacterClass>,
+    /// Compare names case-insensitively and after Unicode normalization, ignoring spaces and
+    /// punctuation, when checking that a name is not already taken.
+    pub normalized_uniqueness: bool,
+    pub rename_cooldown_secs: u64,
+    pub require_approval: bool,
+}
+
+impl Default for TeamNameConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 3,
+            max_length: 24,
+            allowed_characters: vec![
+                NameCharacterClass::Letters,
+                NameCharacterClass::Digits,
+                NameCharacterClass::Spaces,
+                NameCharacterClass::Punctuation,
+            ],
+            normalized_uniqueness: true,
+            rename_cooldown_secs: 60,
+            require_approval: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NameCharacterClass {
+    /// ASCII letters only.
+    AsciiLetters,
+    /// Any Unicode letter, including accented ones.
+    Letters,
+    Digits,
+    Spaces,
+    /// ASCII punctuation and symbols.
+    Punctuation,
+    /// Any other printable character, such as emojis.
+    Other,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutboundConfig {
+    /// Number of messages waiting to be written to a client before it is considered slow.
+    pub queue_size: usize,
+    pub slow_client_policy: SlowClientPolicy,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            queue_size: 64,
+            slow_client_policy: SlowClientPolicy::DropStale,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SlowClientPolicy {
+    /// Replace the game states a client hasn't received yet by the newest one, and drop new
+    /// messages while its queue is full.
+    DropStale,
+    /// Disconnect clients once their queue is full.
+    Disconnect,
+}
+
+/// What happens when an agent links to a team that already has a linked agent.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateAgentPolicy {
+    /// The new agent replaces the old one, which is notified and disconnected.
+    #[default]
+    Replace,
+    /// The new agent is refused while the old one is linked.
+    Reject,
+    /// Every agent is linked, the last action received wins.
+    Allow,
+}
+
+/// Limits on the messages received from each client, 0 disables a limit.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Size in bytes above which messages are ignored.
+    pub max_message_size: usize,
+    /// Messages accepted per second, with bursts of up to one second worth of messages.
+    pub messages_per_second: u32,
+    /// Warnings sent for ignored messages, at most one per second, before the client is
+    /// disconnected. They are forgotten once no message was ignored for `warning_reset_secs`, so
+    /// only a client that keeps flooding is disconnected.
+    pub max_warnings: u32,
+    /// 0 keeps the warnings for the whole connection.
+    pub warning_reset_secs: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16 * 1024,
+            messages_per_second: 20,
+            max_warnings: 10,
+            warning_reset_secs: 60,
+        }
+    }
+}
+
+/// Phase between two matches, where teams wait until enough of them are ready to play.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LobbyConfig {
+    /// Teams with a linked agent or marked ready needed to start a match, at least 2 unless
+    /// `allow_single_player` is set.
+    pub min_players: usize,
+    /// Seconds announced to the clients before the first tick of a match.
+    pub countdown_secs: u64,
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        Self {
+            min_players: 2,
+            countdown_secs: 5,
+        }
+    }
+}
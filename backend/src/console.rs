@@ -1,11 +1,16 @@
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 
 use log::{LevelFilter, SetLoggerError};
 use rustyline_async::{Readline, ReadlineEvent};
+use serde_json::json;
 
-use crate::server::{Server, ServerMessage};
-use crate::{PAUSE_GAME, SHOULD_STOP, STOP_INTERRUPT};
+use crate::save::Save;
+use crate::scenario::Scenario;
+use crate::server::{ErrorCode, Server, ServerMessage};
+use crate::snapshot::Snapshot;
+use crate::{PAUSE_GAME, SHOULD_STOP, stop, teams};
 
 pub fn start_cli(mut rl: Readline, server: Arc<Server>) {
     tokio::spawn(async move {
@@ -26,6 +31,12 @@ pub fn start_cli(mut rl: Readline, server: Arc<Server>) {
                         "list" => {
                             server.game.lock().await.list_players();
                         }
+                        "clients" => {
+                            server.list_clients().await;
+                        }
+                        "connections" => {
+                            server.list_connections().await;
+                        }
                         "add" => {
                             if args.len() < 2 {
                                 log::error!("Usage: add <team_name>");
@@ -33,8 +44,41 @@ pub fn start_cli(mut rl: Readline, server: Arc<Server>) {
                             }
 
                             let name = args[1..].join(" ");
-                            let id = server.game.lock().await.create_player(name.clone());
-                            log::info!("Team {} added with id {}", name, *id);
+                            match server.game.lock().await.create_player(name.clone()) {
+                                Some(id) => log::info!("Team {} added with id {}", name, *id),
+                                None => log::error!("A team named {} already exists", name),
+                            }
+                        }
+                        "import" => {
+                            if args.len() < 2 || args.len() > 3 {
+                                log::error!("Usage: import <teams.csv|teams.json> [tokens.csv]");
+                                continue;
+                            }
+
+                            let output = args.get(2).copied().unwrap_or(teams::DEFAULT_TOKEN_SHEET);
+                            let names = match teams::read_team_list(Path::new(args[1])) {
+                                Ok(names) => names,
+                                Err(e) => {
+                                    log::error!("{}", e);
+                                    continue;
+                                }
+                            };
+
+                            let result = teams::import_teams(&mut *server.game.lock().await, names)
+                                .and_then(|teams| {
+                                    teams::write_token_sheet(Path::new(output), &teams)?;
+                                    Ok(teams.len())
+                                });
+                            match result {
+                                Ok(count) => {
+                                    log::info!(
+                                        "Imported {} teams, tokens written to {}",
+                                        count,
+                                        output
+                                    )
+                                }
+                                Err(e) => log::error!("{}", e),
+                            }
                         }
                         "score" => {
                             if args.len() < 3 {
@@ -54,22 +98,179 @@ pub fn start_cli(mut rl: Readline, server: Arc<Server>) {
                                 log::error!("No team with given name");
                             }
                         }
-                        "pause" => {
-                            log::debug!("Pausing...");
-                            PAUSE_GAME.store(true, Ordering::Relaxed);
+                        "ban" | "unban" => {
+                            if args.len() < 2 {
+                                log::error!("Usage: {} <team_name>", args[0]);
+                                continue;
+                            }
+
+                            let name = args[1..].join(" ");
+                            let banned = args[0] == "ban";
+                            match server.game.lock().await.set_player_banned(&name, banned) {
+                                Some(id) => {
+                                    log::info!("Team {} {}ned", name, args[0]);
+                                    if banned {
+                                        server.unlink_team(id);
+                                    }
+                                }
+                                None => log::error!("No team with given name"),
+                            }
+                        }
+                        "ready" | "unready" => {
+                            if args.len() < 2 {
+                                log::error!("Usage: {} <team_name>", args[0]);
+                                continue;
+                            }
+
+                            let name = args[1..].join(" ");
+                            let ready = args[0] == "ready";
+                            if server.game.lock().await.set_player_ready(&name, ready) {
+                                log::info!("Team {} marked {}", name, args[0]);
+                            } else {
+                                log::error!("No team with given name");
+                            }
+                        }
+                        "renames" => {
+                            let game = server.game.lock().await;
+                            if game.pending_renames().is_empty() {
+                                log::info!("No pending renames");
+                            }
+                            for (i, rename) in game.pending_renames().iter().enumerate() {
+                                log::info!(
+                                    "{}. {} -> {}",
+                                    i,
+                                    game.get_player_name(&rename.id).map_or("?", |n| n),
+                                    rename.name
+                                );
+                            }
+                        }
+                        "approve" => {
+                            let Some(Ok(index)) = args.get(1).map(|i| i.parse()) else {
+                                log::error!("Usage: approve <rename_number>");
+                                continue;
+                            };
+
+                            match server.game.lock().await.approve_rename(index) {
+                                Some(Ok(rename)) => {
+                                    log::info!("Team renamed to {}", rename.name);
+                                    server.send_message_dashboard(
+                                        rename.id,
+                                        ServerMessage::NameConfirmation { name: rename.name },
+                                    );
+                                }
+                                Some(Err(e)) => log::error!("Could not approve rename: {}", e),
+                                None => log::error!("No pending rename with this number"),
+                            }
+                        }
+                        "reject" => {
+                            let Some(Ok(index)) = args.get(1).map(|i| i.parse()) else {
+                                log::error!("Usage: reject <rename_number>");
+                                continue;
+                            };
+
+                            match server.game.lock().await.reject_rename(index) {
+                                Some(rename) => {
+                                    log::info!("Rejected rename to {}", rename.name);
+                                    server.send_message_dashboard(
+                                        rename.id,
+                                        ServerMessage::error_with_context(
+                                            ErrorCode::InvalidRename,
+                                            "Rejected by an organizer",
+                                            json!({ "name": rename.name }),
+                                        ),
+                                    );
+                                }
+                                None => log::error!("No pending rename with this number"),
+                            }
                         }
-                        "resume" => {
-                            log::debug!("Resuming game...");
-                            PAUSE_GAME.store(false, Ordering::Relaxed);
+                        "pause" | "resume" => {
+                            let paused = args[0] == "pause";
+                            if PAUSE_GAME.swap(paused, Ordering::Relaxed) == paused {
+                                log::warn!(
+                                    "{}",
+                                    if paused {
+                                        "The game is already paused"
+                                    } else {
+                                        "The game is not paused"
+                                    }
+                                );
+                                continue;
+                            }
+
+                            let reason = (args.len() > 1).then(|| args[1..].join(" "));
+                            log::info!("Game {}d", args[0]);
+                            server
+                                .send_message_all(if paused {
+                                    ServerMessage::GamePaused { reason }
+                                } else {
+                                    ServerMessage::GameResumed { reason }
+                                })
+                                .await;
+                            server.send_dashboard_state();
                         }
                         "restart" => {
                             log::debug!("Restarting game...");
+                            // The game loop goes back to the lobby once no team is in the match
                             server.game.lock().await.restart();
                         }
                         "save" => {
                             log::debug!("Saving game...");
                             server.game.lock().await.get_save().save();
                         }
+                        "lastsave" => match Save::last_save() {
+                            Some((time, autosave)) => log::info!(
+                                "Last {} {}s ago",
+                                if autosave { "autosave" } else { "save" },
+                                time.elapsed().map_or(0, |elapsed| elapsed.as_secs())
+                            ),
+                            None => log::info!("Nothing was saved since the server started"),
+                        },
+                        "snapshot" => {
+                            if args.len() != 3 || !matches!(args[1], "save" | "load") {
+                                log::error!("Usage: snapshot <save|load> <file>");
+                                continue;
+                            }
+
+                            let path = Path::new(args[2]);
+                            if args[1] == "save" {
+                                let snapshot = server.game.lock().await.get_snapshot();
+                                match snapshot.write(path) {
+                                    Ok(()) => log::info!("Snapshot written to {}", path.display()),
+                                    Err(e) => log::error!("Failed to write snapshot: {}", e),
+                                }
+                            } else {
+                                match Snapshot::read(path) {
+                                    Ok(snapshot) => {
+                                        server.game.lock().await.restore_snapshot(snapshot);
+                                        // Agents need the rules of the restored match and
+                                        // dashboards a keyframe
+                                        server.send_game_start().await;
+                                    }
+                                    Err(e) => log::error!("Failed to read snapshot: {}", e),
+                                }
+                            }
+                        }
+                        "scenario" => {
+                            if args.len() != 2 {
+                                log::error!("Usage: scenario <file>");
+                                continue;
+                            }
+
+                            let scenario = match Scenario::read(Path::new(args[1])) {
+                                Ok(scenario) => scenario,
+                                Err(e) => {
+                                    log::error!("{}", e);
+                                    continue;
+                                }
+                            };
+
+                            let result = server.game.lock().await.load_scenario(scenario);
+                            match result {
+                                // Agents need the rules of the new match and dashboards a keyframe
+                                Ok(()) => server.send_game_start().await,
+                                Err(e) => log::error!("Could not load scenario: {}", e),
+                            }
+                        }
                         "msg" => {
                             if args.len() < 2 {
                                 log::error!("Usage: msg <message>");
@@ -83,17 +284,43 @@ pub fn start_cli(mut rl: Readline, server: Arc<Server>) {
                         }
                         "exit" => {
                             log::debug!("Exiting...");
-                            SHOULD_STOP.store(true, Ordering::Relaxed);
-                            STOP_INTERRUPT.notify_waiters();
+                            stop();
                         }
                         "help" => {
                             log::info!("Available commands:");
                             log::info!("list - Lists all the teams");
+                            log::info!(
+                                "clients - List the connected clients with their dropped and rejected messages"
+                            );
+                            log::info!(
+                                "connections - Count the agents and dashboards of each team"
+                            );
                             log::info!("add <team_name> - Add a team to the game");
+                            log::info!(
+                                "import <file> [tokens.csv] - Add every team of a CSV/JSON list and write their tokens"
+                            );
                             log::info!("score <quantity> <team_name> - Give score to a team");
-                            log::info!("pause - Pause the game");
-                            log::info!("resume - Un-pause the game");
+                            log::info!("ban <team_name> - Prevent a team from linking");
+                            log::info!("unban <team_name> - Allow a banned team to link again");
+                            log::info!(
+                                "ready <team_name> - Spawn a team in the next matches even without an agent"
+                            );
+                            log::info!(
+                                "unready <team_name> - Only spawn a team with a linked agent"
+                            );
+                            log::info!("renames - List the renames waiting for approval");
+                            log::info!("approve <rename_number> - Approve a pending rename");
+                            log::info!("reject <rename_number> - Reject a pending rename");
+                            log::info!("pause [reason] - Pause the game and notify the clients");
+                            log::info!(
+                                "resume [reason] - Un-pause the game and notify the clients"
+                            );
                             log::info!("save - Force a player save");
+                            log::info!("lastsave - Show when the save file was last written");
+                            log::info!(
+                                "snapshot <save|load> <file> - Write the running match to a file or resume it"
+                            );
+                            log::info!("scenario <file> - Replace the match by a hand-crafted one");
                             log::info!("msg <message> - Send a message to the frontend");
                             log::info!("exit - Exit the server");
                             log::info!("help - Display this help message");
@@ -108,8 +335,7 @@ pub fn start_cli(mut rl: Readline, server: Arc<Server>) {
                 }
                 ReadlineEvent::Eof | ReadlineEvent::Interrupted => {
                     log::info!("Received interrupt...");
-                    SHOULD_STOP.store(true, std::sync::atomic::Ordering::Relaxed);
-                    STOP_INTERRUPT.notify_waiters();
+                    stop();
                     break;
                 }
             }
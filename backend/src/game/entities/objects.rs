@@ -1,10 +1,14 @@
 use std::collections::HashSet;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::game::items::Item;
+use crate::snapshot::SnapshotObject;
 use crate::types::{PlayerId, Position};
 
+/// Health of a new wall.
+pub const WALL_HP: u32 = 100;
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
@@ -26,7 +30,10 @@ impl Object {
     }
 
     pub fn new_wall(position: Position) -> Self {
-        Self::Wall(WallObject { position, hp: 100 })
+        Self::Wall(WallObject {
+            position,
+            hp: WALL_HP,
+        })
     }
 
     pub fn new_trap(owner: PlayerId, position: Position, name: String, damage: u32) -> Self {
@@ -74,6 +81,15 @@ impl Object {
             Object::Trap(trap) => &trap.position,
         }
     }
+
+    /// Type of the object as sent to the clients.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Chest(_) => "chest",
+            Object::Wall(_) => "resistance",
+            Object::Trap(_) => "trap",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -97,7 +113,7 @@ impl ChestObject {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WallObject {
     pub position: Position,
     pub hp: u32,
@@ -109,7 +125,7 @@ impl WallObject {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrapObject {
     pub owner: PlayerId,
     pub name: String,
@@ -117,3 +133,35 @@ pub struct TrapObject {
     pub damage: u32,
     pub active: bool,
 }
+
+impl From<&Object> for SnapshotObject {
+    fn from(object: &Object) -> Self {
+        match object {
+            Object::Chest(chest) => SnapshotObject::Chest {
+                position: chest.position.clone(),
+                items: chest.items.clone(),
+                opened_by: chest.opened_by.clone(),
+            },
+            Object::Wall(wall) => SnapshotObject::Wall(wall.clone()),
+            Object::Trap(trap) => SnapshotObject::Trap(trap.clone()),
+        }
+    }
+}
+
+impl From<SnapshotObject> for Object {
+    fn from(snapshot: SnapshotObject) -> Self {
+        match snapshot {
+            SnapshotObject::Chest {
+                position,
+                items,
+                opened_by,
+            } => Object::Chest(ChestObject {
+                position,
+                items,
+                opened_by,
+            }),
+            SnapshotObject::Wall(wall) => Object::Wall(wall),
+            SnapshotObject::Trap(trap) => Object::Trap(trap),
+        }
+    }
+}
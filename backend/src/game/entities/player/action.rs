@@ -1,8 +1,8 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::types::{CardinalDirection, Direction, Position};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "action")]
 #[serde(rename_all = "camelCase")]
 #[serde(rename_all_fields = "camelCase")]
@@ -15,7 +15,7 @@ pub enum Action {
     Skip,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
 #[serde(rename_all_fields = "camelCase")]
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::TickDuration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "camelCase")]
+#[serde(rename_all_fields = "camelCase")]
+pub enum DamageSource {
+    Player {
+        name: String,
+        weapon: String,
+    },
+    Trap {
+        owner: String,
+        name: String,
+    },
+    Firewall,
+    #[serde(rename = "self")]
+    SelfInflicted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DamageRecord {
+    /// Tick of the match, counted from the spawn of the player.
+    pub tick: TickDuration,
+    pub source: DamageSource,
+    pub amount: u32,
+    pub shield_absorbed: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeathReport {
+    pub killer: DamageSource,
+    pub damage_history: Vec<DamageRecord>,
+    pub placement: usize,
+    pub ticks_survived: TickDuration,
+}
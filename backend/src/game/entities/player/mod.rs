@@ -1,21 +1,28 @@
+use std::collections::VecDeque;
 use std::mem;
+use std::time::Instant;
 
 use serde::Serialize;
 
 use crate::CONFIG;
 use crate::config::WorldGenConfig;
 use crate::game::entities::objects::Object;
-use crate::game::entities::player::inventory::InventoryItem;
 use crate::game::entities::projectile::Projectile;
-use crate::game::items::{BuffEffect, ItemData, ItemPlacedObject, ItemQuantity};
+use crate::game::items::{BuffEffect, Item, ItemData, ItemPlacedObject, ItemQuantity};
 use crate::game::map::Map;
 use crate::save::SavedPlayer;
+use crate::snapshot::SnapshotPlayer;
 use crate::types::{PlayerId, Position};
 
 mod action;
+mod damage;
 mod inventory;
+mod summary;
 
 pub use action::*;
+pub use damage::*;
+pub use inventory::InventoryItem;
+pub use summary::*;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Player {
@@ -44,6 +51,30 @@ pub struct Player {
     total_opened_chests: u32,
     #[serde(skip)]
     total_segfaults: u32,
+    #[serde(skip)]
+    total_games: u32,
+    #[serde(skip)]
+    match_start_rank: Option<usize>,
+    /// Ranks won or lost during the last match that ended.
+    #[serde(skip)]
+    rank_change: i32,
+    #[serde(skip)]
+    connected: bool,
+    /// Marked ready by an organizer, to be spawned in the next match even without an agent.
+    #[serde(skip)]
+    ready: bool,
+    #[serde(skip)]
+    in_match: bool,
+    #[serde(skip)]
+    last_rename: Option<Instant>,
+    #[serde(skip)]
+    banned: bool,
+    #[serde(skip)]
+    damage_history: VecDeque<DamageRecord>,
+    #[serde(skip)]
+    killed_by: Option<DamageSource>,
+    #[serde(skip)]
+    match_stats: MatchStats,
 }
 
 impl Player {
@@ -57,7 +88,19 @@ impl Player {
             total_wins: 0,
             total_opened_chests: 0,
             total_segfaults: 0,
-            hp: config.world_gen.player_health,
+            total_games: 0,
+            match_start_rank: None,
+            rank_change: 0,
+            connected: false,
+            ready: false,
+            in_match: false,
+            last_rename: None,
+            banned: false,
+            damage_history: VecDeque::new(),
+            killed_by: None,
+            match_stats: MatchStats::default(),
+            // Waits for the next match to be spawned
+            hp: 0,
             shield: 0,
             position: Position::new(0, 0),
             last_position: None,
@@ -84,13 +127,64 @@ impl Player {
         self.remaining_damage_ticks = 0;
         self.action = None;
         self.alive_time = 0;
+        self.in_match = true;
+        // Teams spawned without an agent, like the ones of a scenario, don't play the match
+        if self.connected || self.ready {
+            self.total_games += 1;
+        }
+        self.damage_history.clear();
+        self.killed_by = None;
+        self.match_stats = MatchStats {
+            start_score: self.score,
+            ..MatchStats::default()
+        };
 
         self.inventory
             .extend(config.player_items.iter().map(InventoryItem::new));
     }
 
+    /// Replaces the health and items given by `respawn`.
+    pub fn set_loadout(&mut self, hp: u32, shield: u32, items: &[Item]) {
+        self.hp = hp;
+        self.shield = shield;
+        self.inventory = items.iter().map(InventoryItem::new).collect();
+    }
+
+    /// Removes the player from the map until it is spawned in a match again.
+    pub fn leave_match(&mut self) {
+        self.hp = 0;
+        self.shield = 0;
+        self.action = None;
+        self.in_match = false;
+    }
+
+    /// Puts the player back where a snapshot left it in the match. The name, score, totals and
+    /// flags come from the save, which may have changed since the snapshot was taken.
+    pub fn restore_match_state(&mut self, snapshot: SnapshotPlayer) {
+        self.hp = snapshot.hp;
+        self.shield = snapshot.shield;
+        self.position = snapshot.position;
+        self.last_position = snapshot.last_position;
+        self.inventory = snapshot.inventory;
+        self.remaining_haste_ticks = snapshot.remaining_haste_ticks;
+        self.remaining_damage_ticks = snapshot.remaining_damage_ticks;
+        self.action = snapshot.action;
+        self.alive_time = snapshot.alive_time;
+        self.match_start_rank = snapshot.match_start_rank;
+        self.rank_change = snapshot.rank_change;
+        self.in_match = snapshot.in_match;
+        self.damage_history = snapshot.damage_history;
+        self.killed_by = snapshot.killed_by;
+        self.match_stats = snapshot.match_stats;
+    }
+
     pub fn rename(&mut self, name: String) {
         self.name = name;
+        self.last_rename = Some(Instant::now());
+    }
+
+    pub fn last_rename(&self) -> Option<Instant> {
+        self.last_rename
     }
 
     pub fn id(&self) -> &PlayerId {
@@ -121,12 +215,59 @@ impl Player {
         self.total_segfaults
     }
 
+    pub fn total_games(&self) -> u32 {
+        self.total_games
+    }
+
+    pub fn set_match_start_rank(&mut self, rank: usize) {
+        self.match_start_rank = Some(rank);
+    }
+
+    pub fn rank_change(&self) -> i32 {
+        self.rank_change
+    }
+
+    /// Compares the rank at the end of the match with the one it started with.
+    pub fn set_match_end_rank(&mut self, rank: usize) {
+        self.rank_change = self
+            .match_start_rank
+            .map_or(0, |start| start as i32 - rank as i32);
+    }
+
+    pub fn is_banned(&self) -> bool {
+        self.banned
+    }
+
+    pub fn set_banned(&mut self, banned: bool) {
+        self.banned = banned;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn set_connected(&mut self, connected: bool) {
+        self.connected = connected;
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    pub fn set_ready(&mut self, ready: bool) {
+        self.ready = ready;
+    }
+
+    pub fn is_in_match(&self) -> bool {
+        self.in_match
+    }
+
     pub fn position(&self) -> &Position {
         &self.position
     }
 
     pub fn steal_inventory(&mut self) -> Vec<InventoryItem> {
-        mem::replace(&mut self.inventory, Vec::new())
+        mem::take(&mut self.inventory)
     }
 
     pub fn is_alive(&self) -> bool {
@@ -166,23 +307,85 @@ impl Player {
 
     pub fn add_kill(&mut self) {
         self.total_kills += 1;
+        self.match_stats.kills += 1;
+    }
+
+    pub fn add_damage_dealt(&mut self, damage: u32) {
+        self.match_stats.damage_dealt += damage;
+    }
+
+    /// Placement of the player in the current match, 1 being the winner.
+    pub fn set_placement(&mut self, placement: usize) {
+        self.match_stats.placement = Some(placement);
     }
 
     pub fn add_win(&mut self) {
         self.total_wins += 1;
     }
 
-    pub fn take_damage(&mut self, damage: u32, dead_count: u32) {
+    /// Returns the damage absorbed by the health and shield of the player.
+    pub fn take_damage(&mut self, damage: u32, dead_count: u32, source: DamageSource) -> u32 {
         if self.is_alive() {
-            self.hp = self.hp.saturating_sub(damage.saturating_sub(self.shield));
-            self.shield = self.shield.saturating_sub(damage);
+            let shield_absorbed = damage.min(self.shield);
+            let amount = (damage - shield_absorbed).min(self.hp);
+            self.hp -= amount;
+            self.shield -= shield_absorbed;
+            self.match_stats.damage_taken += amount + shield_absorbed;
+            self.record_damage(amount, shield_absorbed, &source);
 
             if !self.is_alive() {
+                self.killed_by = Some(source);
                 self.earn_score(
                     (CONFIG.read().unwrap().score.death_multiplier * dead_count as f32).floor()
                         as i32,
                 );
             }
+
+            amount + shield_absorbed
+        } else {
+            0
+        }
+    }
+
+    fn record_damage(&mut self, amount: u32, shield_absorbed: u32, source: &DamageSource) {
+        let kept_ticks = CONFIG.read().unwrap().death_report_ticks;
+        while self
+            .damage_history
+            .front()
+            .is_some_and(|record| record.tick + kept_ticks <= self.alive_time)
+        {
+            self.damage_history.pop_front();
+        }
+
+        self.damage_history.push_back(DamageRecord {
+            tick: self.alive_time,
+            source: source.clone(),
+            amount,
+            shield_absorbed,
+        });
+    }
+
+    /// Recap of the death of the player, if it died this match.
+    pub fn death_report(&self, placement: usize) -> Option<DeathReport> {
+        Some(DeathReport {
+            killer: self.killed_by.clone()?,
+            damage_history: self.damage_history.iter().cloned().collect(),
+            placement,
+            ticks_survived: self.alive_time,
+        })
+    }
+
+    /// Recap of the current match, `placement` is used if the player has none yet.
+    pub fn match_summary(&self, placement: usize) -> MatchSummary {
+        MatchSummary {
+            placement: self.match_stats.placement.unwrap_or(placement),
+            kills: self.match_stats.kills,
+            damage_dealt: self.match_stats.damage_dealt,
+            damage_taken: self.match_stats.damage_taken,
+            items_used: self.match_stats.items_used,
+            chests_looted: self.match_stats.chests_looted,
+            ticks_survived: self.alive_time,
+            score_gained: self.score - self.match_stats.start_score,
         }
     }
 
@@ -192,7 +395,7 @@ impl Player {
         }
 
         self.alive_time += 1;
-        if self.alive_time % 10 == 0 {
+        if self.alive_time.is_multiple_of(10) {
             self.score += 1;
         }
 
@@ -247,7 +450,8 @@ impl Player {
                 Action::OpenChest { position } => {
                     if let Some(items) = map.open_chest(self.id(), &position) {
                         self.total_opened_chests += 1;
-                        self.add_to_inventory(items.iter().map(|item| InventoryItem::new(item)));
+                        self.match_stats.chests_looted += 1;
+                        self.add_to_inventory(items.iter().map(InventoryItem::new));
                         self.earn_score(CONFIG.read().unwrap().score.loot_chest);
                     }
 
@@ -267,6 +471,7 @@ impl Player {
                             ActionUseItem::Buff => {
                                 if let ItemData::Buff { effect, power } = item.data {
                                     item.use_one();
+                                    self.match_stats.items_used += 1;
                                     match effect {
                                         BuffEffect::Heal => {
                                             self.hp += power;
@@ -317,6 +522,7 @@ impl Player {
                                 } = item.data
                                 {
                                     item.use_one();
+                                    self.match_stats.items_used += 1;
 
                                     PlayerTickResult::Projectile(
                                         pattern
@@ -353,6 +559,7 @@ impl Player {
                                 {
                                     if position.is_inside(&self.position, range) {
                                         item.use_one();
+                                        self.match_stats.items_used += 1;
 
                                         PlayerTickResult::Placed(
                                             pattern
@@ -391,6 +598,7 @@ impl Player {
                             ActionUseItem::Nuke => {
                                 if let ItemData::Nuke { damage } = item.data {
                                     item.use_one();
+                                    self.match_stats.items_used += 1;
 
                                     PlayerTickResult::Nuke {
                                         item_name: name,
@@ -405,6 +613,9 @@ impl Player {
                 }
 
                 Action::SegFault => {
+                    self.match_stats.damage_taken += self.hp + self.shield;
+                    self.record_damage(self.hp, self.shield, &DamageSource::SelfInflicted);
+                    self.killed_by = Some(DamageSource::SelfInflicted);
                     self.hp = 0;
                     self.shield = 0;
                     self.earn_score(CONFIG.read().unwrap().score.get_killed_by_yourself);
@@ -439,10 +650,77 @@ impl From<(PlayerId, SavedPlayer)> for Player {
         player.total_wins = saved.wins;
         player.total_opened_chests = saved.chests;
         player.total_segfaults = saved.segfaults;
+        player.total_games = saved.games;
+        player.banned = saved.banned;
         player
     }
 }
 
+impl From<&Player> for SnapshotPlayer {
+    fn from(player: &Player) -> Self {
+        Self {
+            name: player.name.clone(),
+            score: player.score,
+            hp: player.hp,
+            shield: player.shield,
+            position: player.position.clone(),
+            last_position: player.last_position.clone(),
+            inventory: player.inventory.clone(),
+            remaining_haste_ticks: player.remaining_haste_ticks,
+            remaining_damage_ticks: player.remaining_damage_ticks,
+            action: player.action.clone(),
+            alive_time: player.alive_time,
+            total_kills: player.total_kills,
+            total_wins: player.total_wins,
+            total_opened_chests: player.total_opened_chests,
+            total_segfaults: player.total_segfaults,
+            total_games: player.total_games,
+            match_start_rank: player.match_start_rank,
+            rank_change: player.rank_change,
+            ready: player.ready,
+            in_match: player.in_match,
+            banned: player.banned,
+            damage_history: player.damage_history.clone(),
+            killed_by: player.killed_by.clone(),
+            match_stats: player.match_stats.clone(),
+        }
+    }
+}
+
+impl From<(PlayerId, SnapshotPlayer)> for Player {
+    fn from((id, snapshot): (PlayerId, SnapshotPlayer)) -> Self {
+        Self {
+            id,
+            name: snapshot.name,
+            score: snapshot.score,
+            hp: snapshot.hp,
+            shield: snapshot.shield,
+            position: snapshot.position,
+            last_position: snapshot.last_position,
+            inventory: snapshot.inventory,
+            remaining_haste_ticks: snapshot.remaining_haste_ticks,
+            remaining_damage_ticks: snapshot.remaining_damage_ticks,
+            action: snapshot.action,
+            alive_time: snapshot.alive_time,
+            total_kills: snapshot.total_kills,
+            total_wins: snapshot.total_wins,
+            total_opened_chests: snapshot.total_opened_chests,
+            total_segfaults: snapshot.total_segfaults,
+            total_games: snapshot.total_games,
+            match_start_rank: snapshot.match_start_rank,
+            rank_change: snapshot.rank_change,
+            connected: false,
+            ready: snapshot.ready,
+            in_match: snapshot.in_match,
+            last_rename: None,
+            banned: snapshot.banned,
+            damage_history: snapshot.damage_history,
+            killed_by: snapshot.killed_by,
+            match_stats: snapshot.match_stats,
+        }
+    }
+}
+
 pub enum PlayerTickResult {
     Projectile(Vec<Projectile>),
     Placed(Vec<Object>),
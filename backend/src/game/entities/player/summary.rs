@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::TickDuration;
+
+/// Counters of the current match, reset when the player respawns.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchStats {
+    pub start_score: i32,
+    pub placement: Option<usize>,
+    pub kills: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub items_used: u32,
+    pub chests_looted: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchSummary {
+    pub placement: usize,
+    pub kills: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub items_used: u32,
+    pub chests_looted: u32,
+    pub ticks_survived: TickDuration,
+    pub score_gained: i32,
+}
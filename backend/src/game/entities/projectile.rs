@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+use crate::snapshot::SnapshotProjectile;
 use crate::types::{Direction, PlayerId, Position, TickDuration};
 
 #[derive(Debug, Clone, Serialize)]
@@ -92,3 +93,31 @@ impl Projectile {
         positions
     }
 }
+
+impl From<&Projectile> for SnapshotProjectile {
+    fn from(projectile: &Projectile) -> Self {
+        Self {
+            owner: projectile.owner.clone(),
+            name: projectile.name.clone(),
+            position: projectile.position.clone(),
+            direction: projectile.direction,
+            remaining_ticks: projectile.remaining_ticks,
+            speed: projectile.speed,
+            damage: projectile.damage,
+        }
+    }
+}
+
+impl From<SnapshotProjectile> for Projectile {
+    fn from(snapshot: SnapshotProjectile) -> Self {
+        Self {
+            owner: snapshot.owner,
+            name: snapshot.name,
+            position: snapshot.position,
+            direction: snapshot.direction,
+            remaining_ticks: snapshot.remaining_ticks,
+            speed: snapshot.speed,
+            damage: snapshot.damage,
+        }
+    }
+}
@@ -8,6 +8,8 @@ pub struct Item {
     pub name: String,
     pub cooldown: TickDuration,
     pub quantity: ItemQuantity,
+    /// Chance of the item to be drawn in chests, relative to the other chest items.
+    #[serde(default)]
     pub draw_weight: f64,
     pub data: ItemData,
 }
@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::Path;
+
+use crate::types::Position;
+
+/// Hand-authored map in ASCII, one character per cell:
+///
+/// - `.` empty cell
+/// - `#` wall
+/// - `X` via
+/// - `C` chest, filled with the chest items of the config
+/// - `S` spawn point
+/// - `F` starting cell of the firewall
+pub struct MapFile {
+    pub width: u32,
+    pub height: u32,
+    pub walls: Vec<Position>,
+    pub vias: Vec<Position>,
+    pub chests: Vec<Position>,
+    /// Cells the teams spawn on. Any free cell is used if there are none.
+    pub spawns: Vec<Position>,
+    /// Starting cells of the firewall. The pattern of the config is used if there are none.
+    pub firewall: Vec<Position>,
+}
+
+impl MapFile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid map {}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let rows = text
+            .trim_end()
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 {
+            return Err("the map is empty".to_string());
+        }
+
+        let mut map = Self {
+            width: width as u32,
+            height: rows.len() as u32,
+            walls: Vec::new(),
+            vias: Vec::new(),
+            chests: Vec::new(),
+            spawns: Vec::new(),
+            firewall: Vec::new(),
+        };
+
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(format!(
+                    "line {} has {} cells instead of {}",
+                    y + 1,
+                    row.chars().count(),
+                    width
+                ));
+            }
+
+            for (x, cell) in row.chars().enumerate() {
+                let position = Position::new(x as i32, y as i32);
+                match cell {
+                    '.' => {}
+                    '#' => map.walls.push(position),
+                    'X' => map.vias.push(position),
+                    'C' => map.chests.push(position),
+                    'S' => map.spawns.push(position),
+                    'F' => map.firewall.push(position),
+                    _ => {
+                        return Err(format!(
+                            "unknown cell '{}' at line {}, column {}",
+                            cell,
+                            y + 1,
+                            x + 1
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_cell() {
+        let map = MapFile::parse("S.#\nXCF\n").unwrap();
+        assert_eq!((map.width, map.height), (3, 2));
+        assert_eq!(map.spawns, vec![Position::new(0, 0)]);
+        assert_eq!(map.walls, vec![Position::new(2, 0)]);
+        assert_eq!(map.vias, vec![Position::new(0, 1)]);
+        assert_eq!(map.chests, vec![Position::new(1, 1)]);
+        assert_eq!(map.firewall, vec![Position::new(2, 1)]);
+    }
+
+    #[test]
+    fn rejects_ragged_lines() {
+        let error = MapFile::parse("...\n..\n").err();
+        assert_eq!(error.as_deref(), Some("line 2 has 2 cells instead of 3"));
+    }
+
+    #[test]
+    fn rejects_unknown_cells() {
+        let error = MapFile::parse("..\n.?\n").err();
+        assert_eq!(
+            error.as_deref(),
+            Some("unknown cell '?' at line 2, column 2")
+        );
+    }
+
+    #[test]
+    fn leaves_spawns_and_firewall_to_the_fallbacks() {
+        let map = MapFile::parse("#.\n.C\n").unwrap();
+        assert!(map.spawns.is_empty());
+        assert!(map.firewall.is_empty());
+    }
+}
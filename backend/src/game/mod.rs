@@ -1,68 +1,214 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use noise::NoiseFn;
-use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::{Rng, SeedableRng, thread_rng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 
-use crate::CONFIG;
-use crate::config::Config;
+use crate::config::{Config, TeamNameConfig, WorldGenConfig};
 use crate::game::entities::Object;
-use crate::game::entities::player::{Action, Player, PlayerTickResult};
+use crate::game::entities::objects::{ChestObject, WallObject};
+use crate::game::entities::player::{
+    Action, DamageSource, DeathReport, MatchSummary, Player, PlayerTickResult,
+};
+use crate::game::items::Item;
 use crate::game::map::Map;
+use crate::game::map_file::MapFile;
+use crate::game::names::{
+    PendingRename, RenameError, RenameOutcome, clean_name, name_key, validate_name,
+};
 use crate::save::Save;
+use crate::scenario::Scenario;
 use crate::server::ServerMessage;
-use crate::server::state::{GameState, GameStats, PlayerGameState, PlayerView, PlayerViewCell};
+use crate::server::state::{
+    GameState, GameStats, MatchInfo, PlayerGameState, PlayerView, PlayerViewCell,
+};
+use crate::snapshot::{Snapshot, SnapshotMap};
 use crate::types::{CardinalDirection, Event, FirewallPattern, PlayerId, Position, ScoreboardTeam};
+use crate::{CONFIG, PAUSE_GAME};
 
 pub mod entities;
 pub mod items;
 pub mod map;
+pub mod map_file;
+pub mod names;
+
+/// Number of cells an agent can see in every direction around itself.
+pub const VISION_RADIUS: u32 = 3;
 
 pub struct Game {
     players: HashMap<PlayerId, Player>,
     map: Map,
+    seed: u64,
+    /// Ticks since the start of the match.
+    tick: u64,
+    /// Generator of the match, seeded with `seed` and used after the map generation to spawn
+    /// the teams and spread the firewall.
+    rng: ChaCha12Rng,
+    /// Free cells of the map to spawn the teams on when the match starts.
+    spawn_cells: Vec<Position>,
+    /// Index of the next map of the rotation of the config.
+    map_rotation: usize,
+    pending_renames: Vec<PendingRename>,
+    death_reports: Vec<(PlayerId, DeathReport)>,
+    match_summaries: Vec<(PlayerId, MatchSummary)>,
 }
 
 impl Game {
     pub fn new() -> Self {
-        let players = Save::load()
+        let mut game = Self::from_save(Save::load());
+        match Snapshot::load() {
+            Some(snapshot) => {
+                log::info!("Resuming the match of the last run from its snapshot");
+                game.restore_snapshot(snapshot);
+                // Agents need time to reconnect before the match goes on without them
+                PAUSE_GAME.store(true, Ordering::Relaxed);
+                log::warn!("The match is paused, resume it once the agents are back");
+            }
+            None => game.restart(),
+        }
+        game
+    }
+
+    /// Game with the teams of the save, without any match.
+    fn from_save(save: Save) -> Self {
+        let players = save
             .players
             .into_iter()
             .map(|saved| (saved.0.clone(), Player::from(saved)))
             .collect();
 
-        let mut game = Self {
+        Self {
             players,
             map: Map::new(),
-        };
-
-        game.restart();
-        game
+            seed: 0,
+            tick: 0,
+            rng: ChaCha12Rng::seed_from_u64(0),
+            spawn_cells: Vec::new(),
+            map_rotation: 0,
+            pending_renames: Vec::new(),
+            death_reports: Vec::new(),
+            match_summaries: Vec::new(),
+        }
     }
 
     pub fn get_player_name(&self, id: &PlayerId) -> Option<&String> {
         self.players.get(id).map(|p| p.name())
     }
 
-    pub fn create_player(&mut self, name: String) -> PlayerId {
+    pub fn get_player_id(&self, name: &str) -> Option<&PlayerId> {
+        self.players
+            .values()
+            .find(|p| p.name() == name)
+            .map(|p| p.id())
+    }
+
+    pub fn create_player(&mut self, name: String) -> Option<PlayerId> {
+        if self.get_player_id(&name).is_some() {
+            return None;
+        }
+
         let id = PlayerId::new();
         self.players
             .insert(id.clone(), Player::new(id.clone(), name));
-        id
+        Some(id)
     }
 
-    pub fn rename_player(&mut self, id: &PlayerId, name: String) -> bool {
-        if name.len() < 3 || self.players.values().any(|p| p.name() == &name) {
-            return false;
+    /// Validates a rename requested by a team, then applies it or queues it for approval.
+    pub fn rename_player(
+        &mut self,
+        id: &PlayerId,
+        name: String,
+    ) -> Result<RenameOutcome, RenameError> {
+        let rules = CONFIG.read().unwrap().team_names.clone();
+        let name = clean_name(&name);
+        let player = self.players.get(id).ok_or(RenameError::UnknownTeam)?;
+
+        let cooldown = Duration::from_secs(rules.rename_cooldown_secs);
+        if let Some(elapsed) = player.last_rename().map(|time| time.elapsed())
+            && elapsed < cooldown
+        {
+            return Err(RenameError::Cooldown {
+                remaining: cooldown - elapsed,
+            });
         }
 
-        if let Some(player) = self.players.get_mut(id) {
-            player.rename(name);
-            true
+        validate_name(&name, &rules)?;
+        self.check_name_available(id, &name, &rules)?;
+
+        if rules.require_approval {
+            log::info!(
+                "Team {} requested to be renamed to {}",
+                self.players[id].name(),
+                name
+            );
+            self.pending_renames.retain(|rename| rename.id != *id);
+            self.pending_renames.push(PendingRename {
+                id: id.clone(),
+                name: name.clone(),
+            });
+            return Ok(RenameOutcome::Pending(name));
+        }
+
+        self.players.get_mut(id).unwrap().rename(name.clone());
+        Ok(RenameOutcome::Renamed(name))
+    }
+
+    fn check_name_available(
+        &self,
+        id: &PlayerId,
+        name: &str,
+        rules: &TeamNameConfig,
+    ) -> Result<(), RenameError> {
+        let key = name_key(name, rules.normalized_uniqueness);
+        let taken = self
+            .players
+            .values()
+            .filter(|p| p.id() != id)
+            .map(|p| p.name())
+            .chain(
+                self.pending_renames
+                    .iter()
+                    .filter(|rename| rename.id != *id)
+                    .map(|rename| &rename.name),
+            )
+            .any(|other| name_key(other, rules.normalized_uniqueness) == key);
+
+        if taken {
+            Err(RenameError::AlreadyTaken)
         } else {
-            false
+            Ok(())
+        }
+    }
+
+    pub fn pending_renames(&self) -> &[PendingRename] {
+        &self.pending_renames
+    }
+
+    /// Applies a pending rename, or returns `None` if there is none with this index.
+    pub fn approve_rename(&mut self, index: usize) -> Option<Result<PendingRename, RenameError>> {
+        if index >= self.pending_renames.len() {
+            return None;
         }
+
+        let rename = self.pending_renames.remove(index);
+        let rules = CONFIG.read().unwrap().team_names.clone();
+        if let Err(e) = self.check_name_available(&rename.id, &rename.name, &rules) {
+            return Some(Err(e));
+        }
+
+        let Some(player) = self.players.get_mut(&rename.id) else {
+            return Some(Err(RenameError::UnknownTeam));
+        };
+        player.rename(rename.name.clone());
+        Some(Ok(rename))
+    }
+
+    pub fn reject_rename(&mut self, index: usize) -> Option<PendingRename> {
+        (index < self.pending_renames.len()).then(|| self.pending_renames.remove(index))
     }
 
     pub fn earn_score(&mut self, name: &String, score: i32) -> bool {
@@ -81,6 +227,32 @@ impl Game {
         }
     }
 
+    pub fn is_player_banned(&self, id: &PlayerId) -> bool {
+        self.players.get(id).is_some_and(|p| p.is_banned())
+    }
+
+    pub fn set_player_ready(&mut self, name: &str, ready: bool) -> bool {
+        match self.players.values_mut().find(|p| p.name() == name) {
+            Some(player) => {
+                player.set_ready(ready);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_player_banned(&mut self, name: &str, banned: bool) -> Option<PlayerId> {
+        let player = self.players.values_mut().find(|p| p.name() == name)?;
+        player.set_banned(banned);
+        Some(player.id().clone())
+    }
+
+    pub fn set_player_connected(&mut self, id: &PlayerId, connected: bool) {
+        if let Some(player) = self.players.get_mut(id) {
+            player.set_connected(connected);
+        }
+    }
+
     pub fn set_player_action(&mut self, id: &PlayerId, action: Action) {
         if let Some(player) = self.players.get_mut(id) {
             player.set_action(action);
@@ -97,10 +269,70 @@ impl Game {
         }
     }
 
+    /// Rank of every team by score, where tied teams share the same rank.
+    fn get_ranks(&self) -> HashMap<PlayerId, usize> {
+        self.players
+            .iter()
+            .map(|(id, player)| {
+                let higher = self
+                    .players
+                    .values()
+                    .filter(|p| p.score() > player.score())
+                    .count();
+                (id.clone(), higher + 1)
+            })
+            .collect()
+    }
+
+    pub fn get_scoreboard(&self) -> Vec<ScoreboardTeam> {
+        let ranks = self.get_ranks();
+        let mut scoreboard = self
+            .players
+            .iter()
+            .map(|(id, player)| {
+                let rank = ranks[id];
+                ScoreboardTeam {
+                    name: player.name().clone(),
+                    score: player.score(),
+                    kills: player.total_kills(),
+                    wins: player.total_wins(),
+                    chests: player.total_chests(),
+                    segfaults: player.total_segfaults(),
+                    games_played: player.total_games(),
+                    rank,
+                    rank_change: player.rank_change(),
+                    connected: player.is_connected(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        scoreboard.sort_by(|a, b| a.rank.cmp(&b.rank).then_with(|| a.name.cmp(&b.name)));
+        scoreboard
+    }
+
+    pub fn get_match_info(&self) -> MatchInfo {
+        let config = CONFIG.read().unwrap();
+        MatchInfo {
+            width: self.map.width,
+            height: self.map.height,
+            seed: config.world_gen.public_seed.then_some(self.seed),
+            firewall_pattern: config.world_gen.firewall_pattern.clone(),
+            firewall_speed: config.world_gen.firewall_speed,
+            firewall_damage: config.firewall_damage,
+            vision_radius: VISION_RADIUS,
+            tick_duration_ms: config.tick_duration_ms,
+            player_health: config.world_gen.player_health,
+            damage_multiplier: config.damage_multiplier,
+            scoring: (&config.score).into(),
+            player_items: config.world_gen.player_items.clone(),
+            chest_items: config.world_gen.chest_items.clone(),
+        }
+    }
+
     pub fn get_game_state(&self) -> ServerMessage {
         let mut ground = vec![PlayerViewCell::Empty; (self.map.width * self.map.height) as usize];
         let mut set_ground_type = |pos: &Position, cell: PlayerViewCell| {
-            ground[(pos.x + pos.y * self.map.height as i32) as usize] = cell;
+            ground[(pos.x + pos.y * self.map.width as i32) as usize] = cell;
         };
 
         for object in &self.map.objects {
@@ -122,16 +354,9 @@ impl Game {
         }
 
         ServerMessage::GameState {
-            scoreboard: CONFIG.read().unwrap().ranked.then(|| {
-                self.players
-                    .values()
-                    .map(|player| ScoreboardTeam {
-                        name: player.name().clone(),
-                        score: player.score(),
-                    })
-                    .collect()
-            }),
+            scoreboard: CONFIG.read().unwrap().ranked.then(|| self.get_scoreboard()),
             state: GameState {
+                paused: PAUSE_GAME.load(Ordering::Relaxed),
                 players: self.players.values().cloned().collect(),
                 stats: GameStats {
                     alive_count: self.players.values().filter(|p| p.is_alive()).count(),
@@ -166,7 +391,7 @@ impl Game {
             .map
             .objects
             .iter()
-            .filter(|o| o.position().is_inside(player.position(), 3))
+            .filter(|o| o.position().is_inside(player.position(), VISION_RADIUS))
             .filter(|o| match o {
                 Object::Chest(chest) => !chest.opened_by(player.id()),
                 Object::Trap(trap) => trap.owner == *player.id(),
@@ -180,7 +405,7 @@ impl Game {
             .values()
             .filter(|p| p.is_alive())
             .filter(|p| p.id() != player.id())
-            .filter(|p| p.position().is_inside(player.position(), 3))
+            .filter(|p| p.position().is_inside(player.position(), VISION_RADIUS))
             .cloned()
             .collect();
 
@@ -188,7 +413,7 @@ impl Game {
             .map
             .projectiles
             .iter()
-            .filter(|p| p.position().is_inside(player.position(), 3))
+            .filter(|p| p.position().is_inside(player.position(), VISION_RADIUS))
             .filter(|p| p.owner() != player.id())
             .cloned()
             .collect();
@@ -199,11 +424,11 @@ impl Game {
         };
 
         let ground = PlayerView {
-            width: 7,
-            height: 7,
+            width: VISION_RADIUS * 2 + 1,
+            height: VISION_RADIUS * 2 + 1,
             data: player
                 .position()
-                .region(3)
+                .region(VISION_RADIUS)
                 .iter()
                 .map(|(x, y)| {
                     let pos = Position::new(*x, *y);
@@ -213,10 +438,10 @@ impl Game {
                         PlayerViewCell::Firewall
                     } else if self.map.vias.contains(&pos) {
                         PlayerViewCell::Via
-                    } else if let Some(_) = objects
+                    } else if objects
                         .iter()
                         .filter_map(|o| o.get_wall())
-                        .find(|o| o.position == pos)
+                        .any(|o| o.position == pos)
                     {
                         PlayerViewCell::Wall
                     } else if let Some(Object::Chest { .. }) = objects
@@ -230,7 +455,7 @@ impl Game {
                     }
                 })
                 .collect(),
-            offset: player.position() - (3, 3),
+            offset: player.position() - (VISION_RADIUS as i32, VISION_RADIUS as i32),
         };
 
         Some(PlayerGameState {
@@ -245,54 +470,78 @@ impl Game {
 
     pub fn restart(&mut self) {
         self.get_save().save();
+        for (id, rank) in self.get_ranks() {
+            if let Some(player) = self.players.get_mut(&id) {
+                player.set_match_start_rank(rank);
+            }
+        }
+
         let config = Config::load();
         *CONFIG.write().unwrap() = config.clone();
 
-        log::debug!("Generating terrain...");
         let config = config.world_gen;
         self.map.clear();
-        self.map.width = config.width;
-        self.map.height = config.height;
+        for player in self.players.values_mut() {
+            player.leave_match();
+        }
 
         let seed = config.seed.unwrap_or(rand::random());
-        let mut rng = StdRng::seed_from_u64(seed);
-        let perlin = noise::Perlin::new(rng.r#gen());
+        self.seed = seed;
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
 
-        // FireWall
-        match config.firewall_pattern {
-            FirewallPattern::OneCorner => {
-                self.map.firewall.insert(
-                    [
-                        Position::new(0, 0),
-                        Position::new(0, config.height as i32 - 1),
-                        Position::new(config.width as i32 - 1, 0),
-                        Position::new(config.width as i32 - 1, config.height as i32 - 1),
-                    ]
-                    .choose(&mut rng)
-                    .unwrap()
-                    .clone(),
-                );
+        let spawn_cells = match self.next_map_file(&config) {
+            Some((path, map_file)) => {
+                let spawn_cells = self.build_map(map_file, &config, &mut rng);
+                log::info!("Map {} loaded using seed {}!", path.display(), seed);
+                let teams = self.lobby_players().len().max(self.min_players());
+                if spawn_cells.len() < teams {
+                    log::warn!(
+                        "Map {} only has {} spawn cells for {} teams, the others will wait for the next match",
+                        path.display(),
+                        spawn_cells.len(),
+                        teams
+                    );
+                }
+                spawn_cells
             }
-            FirewallPattern::FourCorner => {
-                self.map.firewall.extend([
-                    Position::new(0, 0),
-                    Position::new(0, config.height as i32 - 1),
-                    Position::new(config.width as i32 - 1, 0),
-                    Position::new(config.width as i32 - 1, config.height as i32 - 1),
-                ]);
+            None => {
+                let spawn_cells = self.generate_map(&config, &mut rng);
+                log::info!("Map generated using seed {}!", seed);
+                spawn_cells
             }
-            FirewallPattern::Middle => {
-                let middle_x = (config.width / 2) as i32;
-                let middle_y = (config.height / 2) as i32;
-                self.map.firewall.extend([
-                    Position::new(middle_x - 1, middle_y - 1),
-                    Position::new(middle_x - 1, middle_y),
-                    Position::new(middle_x, middle_y - 1),
-                    Position::new(middle_x, middle_y),
-                ]);
+        };
+
+        self.tick = 0;
+        self.spawn_cells = spawn_cells;
+        self.rng = rng;
+        Snapshot::remove();
+    }
+
+    /// Reads the next map of the rotation, or returns `None` to generate one.
+    fn next_map_file(&mut self, config: &WorldGenConfig) -> Option<(PathBuf, MapFile)> {
+        if config.maps.is_empty() {
+            return None;
+        }
+
+        let path = config.maps[self.map_rotation % config.maps.len()].clone();
+        self.map_rotation = (self.map_rotation + 1) % config.maps.len();
+        match MapFile::read(&path) {
+            Ok(map_file) => Some((path, map_file)),
+            Err(e) => {
+                log::error!("{}, generating a map instead", e);
+                None
             }
-            FirewallPattern::None => {}
         }
+    }
+
+    /// Generates the terrain from noise and returns the free cells to spawn the teams on.
+    fn generate_map(&mut self, config: &WorldGenConfig, rng: &mut ChaCha12Rng) -> Vec<Position> {
+        log::debug!("Generating terrain...");
+        self.map.width = config.width;
+        self.map.height = config.height;
+        let perlin = noise::Perlin::new(rng.r#gen());
+
+        self.place_firewall(&config.firewall_pattern, rng);
 
         // Vias and walls
         let mut available_cells = Vec::new();
@@ -328,45 +577,450 @@ impl Game {
             let position = available_cells.swap_remove(index);
 
             log::trace!("Generating chest at {:?}", position);
-            let items_count = rng.gen_range(1..=3);
-            let items = config
-                .chest_items
-                .choose_multiple_weighted(&mut rng, items_count, |i| i.draw_weight)
-                .unwrap()
-                .cloned()
-                .collect();
+            let items = draw_chest_items(config, rng);
             self.map.objects.push(Object::new_chest(position, items));
         }
 
-        log::info!("Map generated using seed {}!", seed);
+        available_cells
+    }
 
-        if available_cells.len() < self.players.len() {
-            log::warn!(
-                "Map is too small for the current number of player! Please increase the map size in the config."
+    /// Builds a hand-authored map and returns its spawn points, or its free cells if it has none.
+    fn build_map(
+        &mut self,
+        map_file: MapFile,
+        config: &WorldGenConfig,
+        rng: &mut ChaCha12Rng,
+    ) -> Vec<Position> {
+        self.map.width = map_file.width;
+        self.map.height = map_file.height;
+        self.map.vias.extend(map_file.vias);
+        self.map
+            .objects
+            .extend(map_file.walls.into_iter().map(Object::new_wall));
+        for position in map_file.chests {
+            let items = draw_chest_items(config, rng);
+            self.map.objects.push(Object::new_chest(position, items));
+        }
+
+        if map_file.firewall.is_empty() {
+            self.place_firewall(&config.firewall_pattern, rng);
+        } else {
+            self.map.firewall.extend(map_file.firewall);
+        }
+
+        if map_file.spawns.is_empty() {
+            self.free_cells()
+        } else {
+            map_file.spawns
+        }
+    }
+
+    fn place_firewall(&mut self, pattern: &FirewallPattern, rng: &mut ChaCha12Rng) {
+        let width = self.map.width as i32;
+        let height = self.map.height as i32;
+        match pattern {
+            FirewallPattern::OneCorner => {
+                self.map.firewall.insert(
+                    [
+                        Position::new(0, 0),
+                        Position::new(0, height - 1),
+                        Position::new(width - 1, 0),
+                        Position::new(width - 1, height - 1),
+                    ]
+                    .choose(rng)
+                    .unwrap()
+                    .clone(),
+                );
+            }
+            FirewallPattern::FourCorner => {
+                self.map.firewall.extend([
+                    Position::new(0, 0),
+                    Position::new(0, height - 1),
+                    Position::new(width - 1, 0),
+                    Position::new(width - 1, height - 1),
+                ]);
+            }
+            FirewallPattern::Middle => {
+                let middle_x = width / 2;
+                let middle_y = height / 2;
+                self.map.firewall.extend([
+                    Position::new(middle_x - 1, middle_y - 1),
+                    Position::new(middle_x - 1, middle_y),
+                    Position::new(middle_x, middle_y - 1),
+                    Position::new(middle_x, middle_y),
+                ]);
+            }
+            FirewallPattern::None => {}
+        }
+    }
+
+    /// Cells without any object, via, firewall or team of the match.
+    fn free_cells(&self) -> Vec<Position> {
+        let occupied = self
+            .map
+            .objects
+            .iter()
+            .map(|o| o.position())
+            .chain(
+                self.players
+                    .values()
+                    .filter(|p| p.is_in_match())
+                    .map(|p| p.position()),
+            )
+            .collect::<HashSet<_>>();
+        (0..self.map.width as i32)
+            .flat_map(|x| (0..self.map.height as i32).map(move |y| Position::new(x, y)))
+            .filter(|pos| {
+                !occupied.contains(pos)
+                    && !self.map.vias.contains(pos)
+                    && !self.map.firewall.contains(pos)
+            })
+            .collect()
+    }
+
+    /// Replaces the match by a hand-crafted scenario instead of a generated map.
+    pub fn load_scenario(&mut self, scenario: Scenario) -> Result<(), String> {
+        let in_bounds = |pos: &Position| {
+            pos.x >= 0
+                && pos.y >= 0
+                && pos.x < scenario.width as i32
+                && pos.y < scenario.height as i32
+        };
+        let positions = scenario
+            .walls
+            .iter()
+            .map(|w| &w.position)
+            .chain(&scenario.vias)
+            .chain(&scenario.firewall)
+            .chain(scenario.chests.iter().map(|c| &c.position))
+            .chain(scenario.traps.iter().map(|t| &t.position))
+            .chain(scenario.players.iter().map(|p| &p.position));
+        for pos in positions {
+            if !in_bounds(pos) {
+                return Err(format!(
+                    "Position ({}, {}) is outside of the map",
+                    pos.x, pos.y
+                ));
+            }
+        }
+
+        let blocked = scenario
+            .walls
+            .iter()
+            .map(|w| &w.position)
+            .chain(&scenario.vias)
+            .chain(&scenario.firewall)
+            .chain(scenario.chests.iter().map(|c| &c.position))
+            .chain(scenario.traps.iter().map(|t| &t.position))
+            .collect::<HashSet<_>>();
+        let mut spawned = HashSet::new();
+        for player in &scenario.players {
+            let pos = &player.position;
+            if blocked.contains(pos) {
+                return Err(format!(
+                    "Team {} can't spawn on the wall, via, firewall, chest or trap at ({}, {})",
+                    player.name, pos.x, pos.y
+                ));
+            }
+            if !spawned.insert(pos) {
+                return Err(format!(
+                    "Team {} can't spawn on another team at ({}, {})",
+                    player.name, pos.x, pos.y
+                ));
+            }
+        }
+
+        let team_id = |name: &String| {
+            self.get_player_id(name)
+                .cloned()
+                .ok_or_else(|| format!("No team named {}", name))
+        };
+        let chests = scenario
+            .chests
+            .into_iter()
+            .map(|chest| {
+                Ok(Object::Chest(ChestObject {
+                    position: chest.position,
+                    items: chest.items,
+                    opened_by: chest
+                        .opened_by
+                        .iter()
+                        .map(team_id)
+                        .collect::<Result<_, _>>()?,
+                }))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let traps = scenario
+            .traps
+            .into_iter()
+            .map(|trap| {
+                Ok(Object::new_trap(
+                    team_id(&trap.owner)?,
+                    trap.position,
+                    trap.name,
+                    trap.damage,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let players = scenario
+            .players
+            .into_iter()
+            .map(|player| Ok((team_id(&player.name)?, player)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        for (id, rank) in self.get_ranks() {
+            if let Some(player) = self.players.get_mut(&id) {
+                player.set_match_start_rank(rank);
+            }
+        }
+
+        self.map.clear();
+        self.map.width = scenario.width;
+        self.map.height = scenario.height;
+        self.map.vias.extend(scenario.vias);
+        self.map.firewall.extend(scenario.firewall);
+        self.map
+            .objects
+            .extend(scenario.walls.into_iter().map(|wall| {
+                Object::Wall(WallObject {
+                    position: wall.position,
+                    hp: wall.hp,
+                })
+            }));
+        self.map.objects.extend(chests);
+        self.map.objects.extend(traps);
+
+        self.seed = scenario.seed.unwrap_or(rand::random());
+        self.rng = ChaCha12Rng::seed_from_u64(self.seed);
+        self.tick = 0;
+        self.death_reports.clear();
+        self.match_summaries.clear();
+
+        for player in self.players.values_mut() {
+            player.leave_match();
+        }
+        let config = CONFIG.read().unwrap().world_gen.clone();
+        for (id, spawn) in &players {
+            let player = self.players.get_mut(id).unwrap();
+            player.respawn(&config, spawn.position.clone());
+            player.set_loadout(
+                spawn.hp.unwrap_or(config.player_health),
+                spawn.shield,
+                spawn.inventory.as_deref().unwrap_or(&config.player_items),
             );
-            self.restart();
-            return;
+        }
+
+        // Free cells for the teams of the lobby if the scenario doesn't spawn any
+        self.spawn_cells = self.free_cells();
+
+        Snapshot::remove();
+        log::info!("Scenario loaded with {} teams", players.len());
+        Ok(())
+    }
+
+    /// Teams that will be spawned when the match starts.
+    pub fn lobby_players(&self) -> Vec<&Player> {
+        self.players
+            .values()
+            .filter(|p| !p.is_banned() && (p.is_connected() || p.is_ready()))
+            .collect()
+    }
+
+    /// Teams needed in the lobby to start a match.
+    pub fn min_players(&self) -> usize {
+        let config = CONFIG.read().unwrap();
+        let required = if config.allow_single_player { 1 } else { 2 };
+        config.lobby.min_players.max(required)
+    }
+
+    /// Names of the teams in the lobby, sorted.
+    pub fn lobby_teams(&self) -> Vec<String> {
+        let mut teams = self
+            .lobby_players()
+            .iter()
+            .map(|p| p.name().clone())
+            .collect::<Vec<_>>();
+        teams.sort();
+        teams
+    }
+
+    pub fn get_lobby(&self) -> ServerMessage {
+        ServerMessage::Lobby {
+            teams: self.lobby_teams(),
+            min_players: self.min_players(),
+        }
+    }
+
+    /// Ticks since the start of the match, identifying the game states sent to clients.
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Whether some teams were spawned since the last restart.
+    pub fn is_match_running(&self) -> bool {
+        self.players.values().any(|p| p.is_in_match())
+    }
+
+    /// Spawns the teams of the lobby on the map generated by the last restart and returns how
+    /// many were spawned.
+    pub fn start_match(&mut self) -> usize {
+        let mut ids = self
+            .lobby_players()
+            .iter()
+            .map(|p| p.id().clone())
+            .collect::<Vec<_>>();
+
+        if self.spawn_cells.len() < ids.len() {
+            let free_cells = self.free_cells();
+            if free_cells.len() > self.spawn_cells.len() {
+                log::warn!("Not enough spawn points on the map, spawning teams on any free cell");
+                self.spawn_cells = free_cells;
+            }
+        }
+        if self.spawn_cells.len() < ids.len() {
+            // Teams left out are drawn at random
+            ids.shuffle(&mut self.rng);
         }
 
         log::debug!("Spawning players");
-        let mut i = 0;
-        while i < self.players.len() {
-            let index = rng.gen_range(0..available_cells.len());
-            let position = available_cells.swap_remove(index);
+        let config = CONFIG.read().unwrap().world_gen.clone();
+        let mut spawned = 0;
+        for id in &ids {
+            let Some(position) = self.take_spawn_cell() else {
+                break;
+            };
+            self.players.get_mut(id).unwrap().respawn(&config, position);
+            spawned += 1;
+        }
+
+        if spawned < ids.len() {
+            let left_out = ids[spawned..]
+                .iter()
+                .filter_map(|id| self.get_player_name(id))
+                .cloned()
+                .collect::<Vec<_>>();
+            log::warn!(
+                "Map is too small for {} teams! {} will wait for the next match: {}",
+                ids.len(),
+                left_out.len(),
+                left_out.join(", ")
+            );
+        }
 
+        log::info!("Match started with {} teams", spawned);
+        spawned
+    }
+
+    /// Removes a random cell a team can stand on from the spawn cells.
+    fn take_spawn_cell(&mut self) -> Option<Position> {
+        while !self.spawn_cells.is_empty() {
+            let index = self.rng.gen_range(0..self.spawn_cells.len());
+            let position = self.spawn_cells.swap_remove(index);
             if self.map.player_can_move_to(&position) {
-                let player = self.players.values_mut().nth(i).unwrap();
-                player.respawn(&config, position);
-                i += 1;
+                return Some(position);
             }
         }
+
+        None
+    }
+
+    /// Complete state of the match, to resume it later with `restore_snapshot`.
+    pub fn get_snapshot(&self) -> Snapshot {
+        Snapshot {
+            seed: self.seed,
+            tick: self.tick,
+            rng: self.rng.clone(),
+            spawn_cells: self.spawn_cells.clone(),
+            map_rotation: self.map_rotation,
+            map: SnapshotMap {
+                width: self.map.width,
+                height: self.map.height,
+                firewall: self.map.firewall.iter().cloned().collect(),
+                vias: self.map.vias.iter().cloned().collect(),
+                objects: self.map.objects.iter().map(Into::into).collect(),
+                projectiles: self.map.projectiles.iter().map(Into::into).collect(),
+            },
+            players: self
+                .players
+                .iter()
+                .map(|(id, p)| (id.clone(), p.into()))
+                .collect(),
+        }
+    }
+
+    /// Replaces the match by the one of the snapshot. Teams missing from the snapshot wait for
+    /// the next match.
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        self.seed = snapshot.seed;
+        self.tick = snapshot.tick;
+        self.rng = snapshot.rng;
+        self.spawn_cells = snapshot.spawn_cells;
+        self.map_rotation = snapshot.map_rotation;
+        self.map.clear();
+        self.map.width = snapshot.map.width;
+        self.map.height = snapshot.map.height;
+        self.map.firewall.extend(snapshot.map.firewall);
+        self.map.vias.extend(snapshot.map.vias);
+        self.map
+            .objects
+            .extend(snapshot.map.objects.into_iter().map(Into::into));
+        self.map
+            .projectiles
+            .extend(snapshot.map.projectiles.into_iter().map(Into::into));
+        self.death_reports.clear();
+        self.match_summaries.clear();
+
+        for player in self.players.values_mut() {
+            player.leave_match();
+        }
+        for (id, saved) in snapshot.players {
+            match self.players.get_mut(&id) {
+                Some(player) => player.restore_match_state(saved),
+                // Removed from the save since, the snapshot is all that is left of the team
+                None => {
+                    self.players.insert(id.clone(), Player::from((id, saved)));
+                }
+            }
+        }
+
+        log::info!(
+            "Restored the match of seed {} at tick {}",
+            self.seed,
+            self.tick
+        );
+    }
+
+    pub fn take_death_reports(&mut self) -> Vec<(PlayerId, DeathReport)> {
+        std::mem::take(&mut self.death_reports)
+    }
+
+    /// Summaries of the match that just ended, for every team that played it.
+    pub fn take_match_summaries(&mut self) -> Vec<(PlayerId, MatchSummary)> {
+        std::mem::take(&mut self.match_summaries)
     }
 
     pub fn tick(&mut self) -> Vec<Event> {
         log::debug!("Ticking game");
+        self.tick += 1;
         let config = CONFIG.read().unwrap();
-        let dead_player_count = self.players.values().filter(|p| !p.is_alive()).count() as u32;
+        let dead_player_count = self
+            .players
+            .values()
+            .filter(|p| p.is_in_match() && !p.is_alive())
+            .count() as u32;
+        let alive_players = self
+            .players
+            .values()
+            .filter(|p| p.is_alive())
+            .map(|p| p.id().clone())
+            .collect::<HashSet<_>>();
+        let names = self
+            .players
+            .iter()
+            .map(|(id, p)| (id.clone(), p.name().clone()))
+            .collect::<HashMap<_, _>>();
         let mut team_kills = Vec::new();
+        let mut damage_dealt = HashMap::<PlayerId, u32>::new();
         let mut events = Vec::new();
         let mut nukes = Vec::new();
 
@@ -381,13 +1035,13 @@ impl Game {
                     self.map.objects.extend(objects);
                 }
                 PlayerTickResult::Nuke { item_name, damage } => {
-                    events.push(Event::new_nuke(player.name().clone()));
+                    events.push(Event::new_nuke(player.id().clone(), player.name().clone()));
                     nukes.push((player.id().clone(), damage, item_name));
                 }
                 PlayerTickResult::SegFault => {
                     events.push(Event::new_kill(
-                        player.name().clone(),
-                        player.name().clone(),
+                        Some((player.id().clone(), player.name().clone())),
+                        (player.id().clone(), player.name().clone()),
                         Some("SegFault".to_owned()),
                     ));
                 }
@@ -411,11 +1065,19 @@ impl Game {
             {
                 trap.active = false; // Trap is triggered
                 player.earn_score(config.score.step_onto_trap);
-                player.take_damage(trap.damage, dead_player_count);
+                *damage_dealt.entry(trap.owner.clone()).or_default() += player.take_damage(
+                    trap.damage,
+                    dead_player_count,
+                    DamageSource::Trap {
+                        owner: names.get(&trap.owner).cloned().unwrap_or_default(),
+                        name: trap.name.clone(),
+                    },
+                );
                 if !player.is_alive() {
                     player.earn_score(config.score.get_killed_by_player);
                     team_kills.push((
                         trap.owner.clone(),
+                        player.id().clone(),
                         player.name().clone(),
                         player.steal_inventory().clone(),
                         Some(trap.name.clone()),
@@ -440,11 +1102,19 @@ impl Game {
                 .values_mut()
                 .filter(|p| p.is_alive() && p.id() != id)
             {
-                victim.take_damage(*damage, dead_player_count);
+                *damage_dealt.entry(id.clone()).or_default() += victim.take_damage(
+                    *damage,
+                    dead_player_count,
+                    DamageSource::Player {
+                        name: names.get(id).cloned().unwrap_or_default(),
+                        weapon: item_name.clone(),
+                    },
+                );
                 if !victim.is_alive() {
                     victim.earn_score(config.score.get_killed_by_player);
                     team_kills.push((
                         id.clone(),
+                        victim.id().clone(),
                         victim.name().clone(),
                         victim.steal_inventory(),
                         Some(item_name.clone()),
@@ -466,11 +1136,22 @@ impl Game {
                     .values_mut()
                     .find(|p| p.is_alive() && *p.position() == proj_pos)
                 {
-                    player.take_damage(proj.damage(), dead_player_count);
+                    let damage = player.take_damage(
+                        proj.damage(),
+                        dead_player_count,
+                        DamageSource::Player {
+                            name: names.get(proj.owner()).cloned().unwrap_or_default(),
+                            weapon: proj.name().clone(),
+                        },
+                    );
+                    if player.id() != proj.owner() {
+                        *damage_dealt.entry(proj.owner().clone()).or_default() += damage;
+                    }
                     if !player.is_alive() {
                         player.earn_score(config.score.get_killed_by_player);
                         team_kills.push((
                             proj.owner().clone(),
+                            player.id().clone(),
                             player.name().clone(),
                             player.steal_inventory().clone(),
                             Some(proj.name().clone()),
@@ -489,10 +1170,10 @@ impl Game {
                     .find(|w| w.position == proj_pos && w.hp > 0)
                 {
                     wall.take_damage(proj.damage());
-                    if wall.hp == 0 {
-                        if let Some(player) = self.players.get_mut(proj.owner()) {
-                            player.earn_score(config.score.break_wall);
-                        }
+                    if wall.hp == 0
+                        && let Some(player) = self.players.get_mut(proj.owner())
+                    {
+                        player.earn_score(config.score.break_wall);
                     }
 
                     proj.mark_for_removal();
@@ -505,7 +1186,7 @@ impl Game {
         let mut new_fire = Vec::new();
         for fire in self.map.firewall.iter() {
             for dir in CardinalDirection::all() {
-                if thread_rng().gen_ratio(1, config.world_gen.firewall_speed) {
+                if self.rng.gen_ratio(1, config.world_gen.firewall_speed) {
                     let pos = fire.with_offset(dir);
                     if self.map.is_within_bounds(&pos) {
                         new_fire.push(pos);
@@ -518,13 +1199,17 @@ impl Game {
         // Firewall damage
         for player in self.players.values_mut().filter(|p| p.is_alive()) {
             if self.map.firewall.contains(player.position()) {
-                player.take_damage(config.firewall_damage, dead_player_count);
+                player.take_damage(
+                    config.firewall_damage,
+                    dead_player_count,
+                    DamageSource::Firewall,
+                );
 
                 if !player.is_alive() {
                     player.earn_score(config.score.get_killed_by_firewall);
                     events.push(Event::new_kill(
-                        "FireWall".to_owned(),
-                        player.name().clone(),
+                        None,
+                        (player.id().clone(), player.name().clone()),
                         None,
                     ));
                 }
@@ -533,7 +1218,13 @@ impl Game {
 
         // Cleanup
         log::debug!("Tick cleanup started");
-        for (killer, victim_name, victim_inventory, weapon) in team_kills {
+        for (id, damage) in damage_dealt {
+            if let Some(player) = self.players.get_mut(&id) {
+                player.add_damage_dealt(damage);
+            }
+        }
+
+        for (killer, victim_id, victim_name, victim_inventory, weapon) in team_kills {
             if let Some(killer) = self.players.get_mut(&killer) {
                 // Check if the weapon used to kill is NOT a nuke weapon
                 // This prevents giving kill score for nuke kills (since nukes affect all players)
@@ -546,20 +1237,37 @@ impl Game {
                 }
 
                 killer.add_kill();
-                events.push(Event::new_kill(killer.name().clone(), victim_name, weapon));
+                events.push(Event::new_kill(
+                    Some((killer.id().clone(), killer.name().clone())),
+                    (victim_id, victim_name),
+                    weapon,
+                ));
                 killer.add_to_inventory(victim_inventory);
             }
         }
 
-        self.map
-            .projectiles
-            .retain(|proj| !proj.should_delete() && Map::new().is_within_bounds(proj.position()));
+        let mut projectiles = std::mem::take(&mut self.map.projectiles);
+        projectiles
+            .retain(|proj| !proj.should_delete() && self.map.is_within_bounds(proj.position()));
+        self.map.projectiles = projectiles;
         self.map.objects.retain(|o| match o {
             Object::Wall(wall) => wall.hp != 0,
             Object::Trap(trap) => trap.active,
             _ => true,
         });
 
+        // Death reports, players who died during the same tick share their placement
+        let placement = self.players.values().filter(|p| p.is_alive()).count() + 1;
+        for id in alive_players {
+            let Some(player) = self.players.get_mut(&id).filter(|p| !p.is_alive()) else {
+                continue;
+            };
+            player.set_placement(placement);
+            if let Some(report) = player.death_report(placement) {
+                self.death_reports.push((id, report));
+            }
+        }
+
         // Game over check
         log::debug!(
             "There are {} players left alive",
@@ -572,13 +1280,26 @@ impl Game {
             if let Some(player) = self.players.values_mut().find(|p| p.is_alive()) {
                 player.earn_score(config.score.victory);
                 player.add_win();
+                player.set_placement(1);
+            }
+            self.match_summaries = self
+                .players
+                .iter()
+                .filter(|(_, p)| p.is_in_match())
+                .map(|(id, p)| (id.clone(), p.match_summary(1)))
+                .collect();
+            // The scoreboard shows these changes until the next match ends
+            for (id, rank) in self.get_ranks() {
+                if let Some(player) = self.players.get_mut(&id) {
+                    player.set_match_end_rank(rank);
+                }
             }
 
             events.push(Event::new_game_end(
                 self.players
                     .values()
                     .find(|p| p.is_alive())
-                    .map(|p| p.name().clone()),
+                    .map(|p| (p.id().clone(), p.name().clone())),
             ));
 
             drop(config); // AVOIDS DEADLOCK!
@@ -588,3 +1309,118 @@ impl Game {
         events
     }
 }
+
+/// Draws between one and three chest items of the config according to their weight.
+fn draw_chest_items(config: &WorldGenConfig, rng: &mut ChaCha12Rng) -> Vec<Item> {
+    let items_count = rng.gen_range(1..=3);
+    config
+        .chest_items
+        .choose_multiple_weighted(rng, items_count, |i| i.draw_weight)
+        .unwrap()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn scenario_game() -> Game {
+        let mut game = Game::from_save(Save::default());
+        game.create_player("Team A".to_owned()).unwrap();
+        game.create_player("Team B".to_owned()).unwrap();
+        game
+    }
+
+    fn scenario(players: &str) -> Scenario {
+        serde_json::from_str(&format!(
+            r#"{{
+                "width": 5,
+                "height": 5,
+                "walls": [{{ "position": {{ "x": 2, "y": 2 }} }}],
+                "chests": [{{ "position": {{ "x": 1, "y": 3 }}, "items": [] }}],
+                "traps": [{{ "owner": "Team B", "name": "Trap", "position": {{ "x": 3, "y": 3 }}, "damage": 20 }}],
+                "players": {}
+            }}"#,
+            players
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn trap_scenario_damages_the_other_team() {
+        let mut game = scenario_game();
+        let scenario = Scenario::read(Path::new("scenarios/trap.json")).unwrap();
+        game.load_scenario(scenario).unwrap();
+
+        let team_a = game.get_player_id("Team A").cloned().unwrap();
+        let team_b = game.get_player_id("Team B").cloned().unwrap();
+        game.set_player_action(
+            &team_b,
+            Action::Move {
+                position: Position::new(3, 2),
+            },
+        );
+        game.tick();
+
+        let snapshot = game.get_snapshot();
+        let (a, b) = (&snapshot.players[&team_a], &snapshot.players[&team_b]);
+        assert_eq!(b.position, Position::new(3, 2));
+        assert_eq!(b.hp + b.shield, 5);
+        assert_eq!(a.hp, CONFIG.read().unwrap().world_gen.player_health);
+    }
+
+    #[test]
+    fn map_without_spawns_uses_free_cells() {
+        let mut game = Game::from_save(Save::default());
+        let config = CONFIG.read().unwrap().world_gen.clone();
+        let map_file = MapFile {
+            width: 3,
+            height: 1,
+            walls: vec![Position::new(0, 0)],
+            vias: vec![Position::new(2, 0)],
+            chests: Vec::new(),
+            spawns: Vec::new(),
+            firewall: Vec::new(),
+        };
+        let spawns = game.build_map(map_file, &config, &mut ChaCha12Rng::seed_from_u64(0));
+
+        assert_eq!(spawns, vec![Position::new(1, 0)]);
+        assert!(!game.map.firewall.is_empty());
+    }
+
+    #[test]
+    fn scenario_rejects_blocked_spawns() {
+        let mut game = scenario_game();
+        let result = game.load_scenario(scenario(
+            r#"[{ "name": "Team A", "position": { "x": 2, "y": 2 } }]"#,
+        ));
+        assert!(result.unwrap_err().contains("wall"));
+    }
+
+    #[test]
+    fn scenario_rejects_spawns_on_objects() {
+        let mut game = scenario_game();
+        for (x, y) in [(1, 3), (3, 3)] {
+            let result = game.load_scenario(scenario(&format!(
+                r#"[{{ "name": "Team A", "position": {{ "x": {}, "y": {} }} }}]"#,
+                x, y
+            )));
+            assert!(result.unwrap_err().contains("chest or trap"));
+        }
+    }
+
+    #[test]
+    fn scenario_rejects_overlapping_spawns() {
+        let mut game = scenario_game();
+        let result = game.load_scenario(scenario(
+            r#"[
+                { "name": "Team A", "position": { "x": 1, "y": 1 } },
+                { "name": "Team B", "position": { "x": 1, "y": 1 } }
+            ]"#,
+        ));
+        assert!(result.unwrap_err().contains("another team"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::time::Duration;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::{NameCharacterClass, TeamNameConfig};
+use crate::types::PlayerId;
+
+#[derive(Debug, Clone)]
+pub struct PendingRename {
+    pub id: PlayerId,
+    pub name: String,
+}
+
+pub enum RenameOutcome {
+    Renamed(String),
+    Pending(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameError {
+    UnknownTeam,
+    TooShort { min: usize },
+    TooLong { max: usize },
+    InvalidCharacter(char),
+    AlreadyTaken,
+    Cooldown { remaining: Duration },
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::UnknownTeam => write!(f, "Unknown team"),
+            RenameError::TooShort { min } => {
+                write!(f, "Name must be at least {} characters long", min)
+            }
+            RenameError::TooLong { max } => {
+                write!(f, "Name must be at most {} characters long", max)
+            }
+            RenameError::InvalidCharacter(c) => {
+                write!(f, "Name contains a forbidden character ({:?})", c)
+            }
+            RenameError::AlreadyTaken => write!(f, "Name is already taken by another team"),
+            RenameError::Cooldown { remaining } => write!(
+                f,
+                "Team was renamed recently, try again in {} seconds",
+                remaining.as_secs() + 1
+            ),
+        }
+    }
+}
+
+/// Trims the name, collapses whitespace runs into single spaces and composes accents.
+pub fn clean_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .nfc()
+        .collect()
+}
+
+pub fn validate_name(name: &str, rules: &TeamNameConfig) -> Result<(), RenameError> {
+    let length = name.chars().count();
+    if length < rules.min_length {
+        return Err(RenameError::TooShort {
+            min: rules.min_length,
+        });
+    }
+    if length > rules.max_length {
+        return Err(RenameError::TooLong {
+            max: rules.max_length,
+        });
+    }
+
+    match name
+        .chars()
+        .find(|c| !is_allowed(*c, &rules.allowed_characters))
+    {
+        Some(c) => Err(RenameError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+/// Key used to detect near-identical names, two names with the same key are considered equal.
+pub fn name_key(name: &str, normalized: bool) -> String {
+    if !normalized {
+        return name.to_owned();
+    }
+
+    let key = name
+        .nfkc()
+        .flat_map(char::to_lowercase)
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>();
+    if key.is_empty() { name.to_owned() } else { key }
+}
+
+fn is_allowed(c: char, classes: &[NameCharacterClass]) -> bool {
+    if c.is_control() || is_invisible(c) {
+        return false;
+    }
+
+    classes.iter().any(|class| match class {
+        NameCharacterClass::AsciiLetters => c.is_ascii_alphabetic(),
+        NameCharacterClass::Letters => c.is_alphabetic(),
+        NameCharacterClass::Digits => c.is_ascii_digit(),
+        NameCharacterClass::Spaces => c == ' ',
+        NameCharacterClass::Punctuation => c.is_ascii_punctuation(),
+        NameCharacterClass::Other => !c.is_whitespace(),
+    })
+}
+
+/// Zero-width, joiner and bidirectional formatting characters.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{206F}'
+            | '\u{FEFF}'
+    )
+}
@@ -0,0 +1,133 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tokio::select;
+
+use crate::server::{Server, ServerMessage};
+use crate::{CONFIG, PAUSE_GAME, SHOULD_STOP, STOP_INTERRUPT};
+
+/// Time between two checks of the teams waiting in the lobby, and between two countdown messages.
+const LOBBY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Waits until enough teams are ready, counts down and spawns them. Returns false if the server
+/// is stopping instead. Returns early if a match was loaded from the console in the meantime.
+pub async fn run(server: &Server) -> bool {
+    log::info!("Waiting for teams in the lobby...");
+    let mut announced = None;
+
+    'lobby: loop {
+        let status = lobby_status(server).await;
+        if status.match_running {
+            log::info!("A match was loaded, leaving the lobby");
+            return true;
+        }
+
+        let teams = status.teams;
+        if announced.as_ref() != Some(&teams) {
+            log::info!(
+                "{}/{} teams in the lobby: {}",
+                teams.len(),
+                status.min_players,
+                teams.join(", ")
+            );
+            announced = Some(teams.clone());
+            server
+                .send_message_all(ServerMessage::Lobby {
+                    teams,
+                    min_players: status.min_players,
+                })
+                .await;
+            continue;
+        }
+
+        if teams.len() < status.min_players {
+            if wait().await == Wake::Stopping {
+                return false;
+            }
+            continue;
+        }
+
+        let countdown_secs = CONFIG.read().unwrap().lobby.countdown_secs;
+        for seconds in (1..=countdown_secs).rev() {
+            log::info!("Match starting in {}...", seconds);
+            server
+                .send_message_all(ServerMessage::Countdown { seconds })
+                .await;
+            match wait().await {
+                Wake::Stopping => return false,
+                Wake::Resumed => {
+                    log::info!("Countdown restarted after the pause");
+                    continue 'lobby;
+                }
+                Wake::Elapsed => {}
+            }
+
+            let status = lobby_status(server).await;
+            if status.match_running {
+                continue 'lobby;
+            }
+            if status.teams.len() < status.min_players {
+                log::info!("Not enough teams left, countdown cancelled");
+                continue 'lobby;
+            }
+        }
+
+        // The game may have been paused after the last check of the countdown
+        if PAUSE_GAME.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let mut game = server.game.lock().await;
+        if game.is_match_running() || game.lobby_players().len() < game.min_players() {
+            continue;
+        }
+        game.start_match();
+        drop(game);
+
+        server.send_game_start().await;
+        return true;
+    }
+}
+
+struct LobbyStatus {
+    teams: Vec<String>,
+    min_players: usize,
+    /// Whether a scenario or a snapshot was loaded from the console.
+    match_running: bool,
+}
+
+async fn lobby_status(server: &Server) -> LobbyStatus {
+    let game = server.game.lock().await;
+    LobbyStatus {
+        teams: game.lobby_teams(),
+        min_players: game.min_players(),
+        match_running: game.is_match_running(),
+    }
+}
+
+#[derive(PartialEq)]
+enum Wake {
+    Elapsed,
+    /// The game was paused during the wait, and resumed since.
+    Resumed,
+    Stopping,
+}
+
+/// Sleeps until the next check, and for as long as the game is paused.
+async fn wait() -> Wake {
+    let mut paused = false;
+    loop {
+        select! {
+            () = tokio::time::sleep(LOBBY_POLL_INTERVAL) => {}
+            () = STOP_INTERRUPT.notified() => {}
+        }
+
+        if SHOULD_STOP.load(Ordering::Relaxed) {
+            return Wake::Stopping;
+        }
+        if !PAUSE_GAME.load(Ordering::Relaxed) {
+            return if paused { Wake::Resumed } else { Wake::Elapsed };
+        }
+        paused = true;
+    }
+}
@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, LazyLock, RwLock};
@@ -9,18 +10,42 @@ use tokio::sync::Notify;
 use tokio_websockets::Error;
 
 use crate::config::Config;
+use crate::game::Game;
 use crate::server::{Server, ServerMessage};
-use crate::types::Event;
+use crate::snapshot::SNAPSHOT_PATH;
 
 mod config;
 mod console;
 mod game;
+mod lobby;
 mod save;
+mod scenario;
 mod server;
+mod snapshot;
+mod stats;
+mod teams;
 mod types;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(command) = args.first() {
+        let result = match command.as_str() {
+            "stats" => stats::run(&args[1..]),
+            "import" => teams::run(&args[1..]),
+            _ => Err(format!(
+                "Unknown command: {}\nAvailable commands: stats, import",
+                command
+            )),
+        };
+
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let rl = console::setup_logging(
         LevelFilter::from_str(&CONFIG.read().unwrap().log_level).unwrap_or(LevelFilter::Info),
     )
@@ -31,35 +56,86 @@ async fn main() -> Result<(), Error> {
 
     let server = Arc::new(Server::new());
     server.listen().await?;
+    listen_for_signals();
 
     if let Some(rl) = rl {
         console::start_cli(rl, server.clone());
     }
 
     // Game loop
-    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    let tick_duration = Duration::from_millis(CONFIG.read().unwrap().tick_duration_ms);
+    let mut interval = tokio::time::interval(tick_duration);
     interval.tick().await;
 
     server.send_game_state();
     interval.tick().await;
 
+    let mut ticks_since_save = 0;
     while !SHOULD_STOP.load(Ordering::Relaxed) {
+        // Waits before ticking, so the state of the last tick is already sent
+        if PAUSE_GAME.load(Ordering::Relaxed) {
+            // Dashboards linking during the pause see it too
+            server.send_dashboard_state();
+            select! {
+                _ = interval.tick() => (),
+                () = STOP_INTERRUPT.notified() => (),
+            }
+            continue;
+        }
+
+        if !server.game.lock().await.is_match_running() {
+            if !lobby::run(&server).await {
+                break;
+            }
+            // The config is reloaded between matches and its tick duration was sent to the agents
+            let tick_duration = Duration::from_millis(CONFIG.read().unwrap().tick_duration_ms);
+            if tick_duration != interval.period() {
+                log::info!("Tick duration changed to {}ms", tick_duration.as_millis());
+                interval = tokio::time::interval(tick_duration);
+            }
+            server.send_game_state();
+            interval.reset();
+            // The match may have been restarted or paused while waiting for the first tick
+            select! {
+                _ = interval.tick() => (),
+                () = STOP_INTERRUPT.notified() => (),
+            }
+            continue;
+        }
+
         log::info!("Running game tick");
 
-        let events = server.game.lock().await.tick();
-        if events
-            .iter()
-            .find(|e| matches!(e, Event::GameEnd { .. }))
-            .is_some()
-        {
-            server.send_message_agent(ServerMessage::GameStart);
+        let (events, death_reports, match_summaries) = {
+            let mut game = server.game.lock().await;
+            (
+                game.tick(),
+                game.take_death_reports(),
+                game.take_match_summaries(),
+            )
+        };
+        for (id, report) in death_reports {
+            server
+                .send_message_agent(&id, ServerMessage::DeathReport { report })
+                .await;
+        }
+        for (id, summary) in match_summaries {
+            server
+                .send_message_agent(&id, ServerMessage::MatchSummary { summary })
+                .await;
         }
         if !events.is_empty() {
-            server.send_message_frontend(ServerMessage::Events { events });
+            server.send_events(events);
         }
 
-        while PAUSE_GAME.load(Ordering::Relaxed) {
-            interval.tick().await;
+        ticks_since_save += 1;
+        let autosave_interval = CONFIG.read().unwrap().autosave_interval_ticks;
+        if autosave_interval > 0 && ticks_since_save >= autosave_interval {
+            log::debug!("Autosaving...");
+            let game = server.game.lock().await;
+            game.get_save().autosave();
+            write_snapshot(&game);
+            drop(game);
+            ticks_since_save = 0;
         }
 
         server.send_game_state();
@@ -70,16 +146,77 @@ async fn main() -> Result<(), Error> {
         }
     }
 
-    server.game.lock().await.get_save().save();
+    log::info!("Saving before shutting down...");
+    let game = server.game.lock().await;
+    game.get_save().save();
+    write_snapshot(&game);
+    drop(game);
+    server.shutdown().await;
 
     log::info!("Goodbye!");
     Ok(())
 }
 
+/// Writes the snapshot the next run of the server resumes the running match from.
+fn write_snapshot(game: &Game) {
+    if !game.is_match_running() {
+        return;
+    }
+
+    if let Err(e) = game.get_snapshot().write(Path::new(SNAPSHOT_PATH)) {
+        log::error!("Failed to write snapshot file. {}", e);
+    }
+}
+
+/// Stops the game loop after the current tick, the server then saves and disconnects clients.
+fn stop() {
+    SHOULD_STOP.store(true, Ordering::Relaxed);
+    STOP_INTERRUPT.notify_waiters();
+}
+
+/// Stops the server on SIGTERM, sent by Docker when stopping the container, and on SIGINT.
+fn listen_for_signals() {
+    tokio::spawn(async {
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(e) => {
+                    log::warn!("Failed to listen for SIGTERM ({})", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        let signal = select! {
+            () = terminate => "SIGTERM",
+            result = tokio::signal::ctrl_c() => match result {
+                Ok(()) => "SIGINT",
+                Err(e) => {
+                    log::warn!("Failed to listen for SIGINT ({})", e);
+                    return;
+                }
+            },
+        };
+
+        log::info!("Received {}, stopping...", signal);
+        stop();
+    });
+}
+
 static SHOULD_STOP: AtomicBool = AtomicBool::new(false);
 static PAUSE_GAME: AtomicBool = AtomicBool::new(false);
 static STOP_INTERRUPT: LazyLock<Notify> = LazyLock::new(Notify::new);
 pub static CONFIG: LazyLock<RwLock<Config>> = LazyLock::new(|| {
-    let config = Config::load();
+    // Tests don't read nor write the config file of the working directory
+    let config = if cfg!(test) {
+        Config::default()
+    } else {
+        Config::load()
+    };
     RwLock::new(config)
 });
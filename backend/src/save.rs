@@ -1,24 +1,21 @@
-use std::{collections::HashMap, fs, time::SystemTime};
+use std::sync::Mutex;
+use std::{collections::HashMap, fs, path::Path, time::SystemTime};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{game::entities::player::Player, types::PlayerId};
 
-const SAVE_PATH: &str = "save.json";
+pub const SAVE_PATH: &str = "save.json";
+pub const HISTORY_DIR: &str = "history";
 
-#[derive(Clone, Serialize, Deserialize)]
+/// Last time the save file was written successfully, and whether it was an autosave.
+static LAST_SAVE: Mutex<Option<(SystemTime, bool)>> = Mutex::new(None);
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Save {
     pub players: HashMap<PlayerId, SavedPlayer>,
 }
 
-impl Default for Save {
-    fn default() -> Self {
-        Self {
-            players: HashMap::new(),
-        }
-    }
-}
-
 impl Save {
     pub fn load() -> Self {
         match fs::read_to_string(SAVE_PATH) {
@@ -31,19 +28,57 @@ impl Save {
         }
     }
 
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid save {}: {}", path.display(), e))
+    }
+
     pub fn save(&self) {
         let data = serde_json::to_string_pretty(self).unwrap();
-        if let Err(e) = fs::write(SAVE_PATH, &data) {
-            log::error!("Failed to write save file. {}", e);
-        }
+        Self::write(&data, false);
 
         let time = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap();
-        if let Err(e) = fs::write(format!("history/save_{}.json", time.as_secs()), &data) {
+        if let Err(e) = fs::write(
+            format!("{}/save_{}.json", HISTORY_DIR, time.as_secs()),
+            &data,
+        ) {
             log::error!("Failed to write backup save file. {}", e);
         }
     }
+
+    /// Writes the save file without adding a copy to the history.
+    pub fn autosave(&self) {
+        let data = serde_json::to_string_pretty(self).unwrap();
+        Self::write(&data, true);
+    }
+
+    /// Last time the save file was written successfully, and whether it was an autosave.
+    pub fn last_save() -> Option<(SystemTime, bool)> {
+        *LAST_SAVE.lock().unwrap()
+    }
+
+    fn write(data: &str, autosave: bool) {
+        match Self::replace(data) {
+            Ok(()) => *LAST_SAVE.lock().unwrap() = Some((SystemTime::now(), autosave)),
+            Err(e) => log::error!("Failed to write save file. {}", e),
+        }
+    }
+
+    /// Renaming replaces the file at once, so a crash while writing can't corrupt the save. A
+    /// save mounted as a single file in Docker can't be renamed over, it is written in place.
+    fn replace(data: &str) -> std::io::Result<()> {
+        let temp_path = format!("{}.tmp", SAVE_PATH);
+        fs::write(&temp_path, data)?;
+        if let Err(e) = fs::rename(&temp_path, SAVE_PATH) {
+            log::debug!("Failed to rename the save file, writing it in place. {}", e);
+            fs::remove_file(&temp_path)?;
+            fs::write(SAVE_PATH, data)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -54,6 +89,26 @@ pub struct SavedPlayer {
     pub wins: u32,
     pub chests: u32,
     pub segfaults: u32,
+    #[serde(default)]
+    pub games: u32,
+    #[serde(default)]
+    pub banned: bool,
+}
+
+impl SavedPlayer {
+    /// Team that never played a match.
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            score: 0,
+            kills: 0,
+            wins: 0,
+            chests: 0,
+            segfaults: 0,
+            games: 0,
+            banned: false,
+        }
+    }
 }
 
 impl From<&Player> for SavedPlayer {
@@ -65,6 +120,8 @@ impl From<&Player> for SavedPlayer {
             wins: value.total_wins(),
             chests: value.total_chests(),
             segfaults: value.total_segfaults(),
+            games: value.total_games(),
+            banned: value.is_banned(),
         }
     }
 }
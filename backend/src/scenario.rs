@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::game::entities::objects::WALL_HP;
+use crate::game::items::Item;
+use crate::types::Position;
+
+/// Hand-crafted match loaded instead of a generated map, to reproduce a precise situation.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scenario {
+    pub width: u32,
+    pub height: u32,
+    /// Seed of the generator spreading the firewall, random if missing.
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub walls: Vec<ScenarioWall>,
+    #[serde(default)]
+    pub vias: Vec<Position>,
+    #[serde(default)]
+    pub firewall: Vec<Position>,
+    #[serde(default)]
+    pub chests: Vec<ScenarioChest>,
+    #[serde(default)]
+    pub traps: Vec<ScenarioTrap>,
+    /// Teams spawned when the scenario is loaded. Without any, the teams of the lobby are
+    /// spawned on the free cells when the next match starts.
+    #[serde(default)]
+    pub players: Vec<ScenarioPlayer>,
+}
+
+impl Scenario {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid scenario {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenarioWall {
+    pub position: Position,
+    #[serde(default = "default_wall_hp")]
+    pub hp: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenarioChest {
+    pub position: Position,
+    pub items: Vec<Item>,
+    /// Teams that already opened the chest.
+    #[serde(default)]
+    pub opened_by: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenarioTrap {
+    /// Team that placed the trap, which doesn't trigger it.
+    pub owner: String,
+    pub name: String,
+    pub position: Position,
+    pub damage: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenarioPlayer {
+    /// Name of an existing team.
+    pub name: String,
+    pub position: Position,
+    /// Defaults to the health of the config.
+    pub hp: Option<u32>,
+    #[serde(default)]
+    pub shield: u32,
+    /// Defaults to the starting items of the config.
+    pub inventory: Option<Vec<Item>>,
+}
+
+fn default_wall_hp() -> u32 {
+    WALL_HP
+}
@@ -1,16 +1,39 @@
 use futures_util::{SinkExt, stream::SplitSink};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex as SyncMutex, RwLock as SyncRwLock};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
-use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+use tokio::select;
+use tokio::sync::{Notify, RwLock, RwLockReadGuard};
+use tokio::time::timeout;
 use tokio_websockets::{Message, WebSocketStream};
 
+use crate::CONFIG;
+use crate::config::SlowClientPolicy;
 use crate::server::ServerMessage;
+use crate::server::limits::{MessageLimiter, Verdict};
+use crate::server::protocol::{self, Session, SharedMessage};
 use crate::types::PlayerId;
 
+/// Time given to a disconnected client to receive the close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub(super) struct Client {
-    sender: Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>,
+    outbound: SyncMutex<VecDeque<Outbound>>,
+    outbound_ready: Notify,
+    closed: Notify,
+    /// Set to disconnect the client once its queued messages are written.
+    closing: AtomicBool,
     state: RwLock<ClientState>,
+    session: SyncRwLock<Session>,
     connected: AtomicBool,
+    dropped_messages: AtomicU64,
+    needs_keyframe: AtomicBool,
+    /// Tick of the last game state queued for the dashboard, the only one a delta can apply to.
+    last_state_tick: SyncMutex<Option<u64>>,
+    last_seen: SyncMutex<Instant>,
+    limiter: SyncMutex<MessageLimiter>,
 }
 
 #[derive(PartialEq, Clone)]
@@ -20,16 +43,56 @@ pub(super) enum ClientState {
     Frontend(Option<PlayerId>),
 }
 
+/// Encoded message waiting to be written to the socket of a client.
+struct Outbound {
+    message: Message,
+    kind: OutboundKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutboundKind {
+    /// A full game state, which makes the previous ones stale.
+    State,
+    /// A game state delta, which needs the previous state to be applied.
+    Delta,
+    Other,
+}
+
+impl OutboundKind {
+    fn of(message: &ServerMessage) -> Self {
+        match message {
+            ServerMessage::TickInfo { .. }
+            | ServerMessage::TickInfoDead
+            | ServerMessage::GameState { .. } => OutboundKind::State,
+            ServerMessage::GameStateDelta { .. } => OutboundKind::Delta,
+            _ => OutboundKind::Other,
+        }
+    }
+
+    fn is_state(self) -> bool {
+        self != OutboundKind::Other
+    }
+}
+
 impl Client {
-    pub fn new(sender: SplitSink<WebSocketStream<TcpStream>, Message>) -> Self {
+    pub fn new() -> Self {
         Self {
-            sender: Mutex::new(sender),
+            outbound: SyncMutex::new(VecDeque::new()),
+            outbound_ready: Notify::new(),
+            closed: Notify::new(),
+            closing: AtomicBool::new(false),
             state: RwLock::new(ClientState::Unregistered),
+            session: SyncRwLock::new(Session::default()),
             connected: AtomicBool::new(true),
+            dropped_messages: AtomicU64::new(0),
+            needs_keyframe: AtomicBool::new(false),
+            last_state_tick: SyncMutex::new(None),
+            last_seen: SyncMutex::new(Instant::now()),
+            limiter: SyncMutex::new(MessageLimiter::new()),
         }
     }
 
-    pub async fn state(&self) -> RwLockReadGuard<ClientState> {
+    pub async fn state(&self) -> RwLockReadGuard<'_, ClientState> {
         self.state.read().await
     }
 
@@ -44,31 +107,181 @@ impl Client {
             .unwrap_or(ClientState::Unregistered)
     }
 
+    pub fn session(&self) -> Session {
+        self.session.read().unwrap().clone()
+    }
+
+    pub fn set_session(&self, session: Session) {
+        *self.session.write().unwrap() = session;
+    }
+
     pub fn is_connected(&self) -> bool {
         self.connected.load(Ordering::Relaxed)
     }
 
-    pub async fn send(&self, message: &ServerMessage) -> bool {
+    /// Stops reading from and writing to the client, which closes its socket.
+    pub fn disconnect(&self) {
+        self.connected.store(false, Ordering::Relaxed);
+        self.outbound_ready.notify_one();
+        self.closed.notify_waiters();
+    }
+
+    /// Disconnects the client after writing the messages already queued.
+    pub fn close(&self) {
+        self.closing.store(true, Ordering::Relaxed);
+        self.outbound_ready.notify_one();
+    }
+
+    /// Completes once the client is disconnected, to interrupt reads and writes that would
+    /// otherwise wait forever on a half-open connection.
+    pub async fn closed(&self) {
+        let closed = self.closed.notified();
+        if self.is_connected() {
+            closed.await;
+        }
+    }
+
+    /// Records that something was received from the client, which proves it is still there.
+    pub fn mark_seen(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    pub fn idle_time(&self) -> Duration {
+        self.last_seen.lock().unwrap().elapsed()
+    }
+
+    /// Queues a ping, the client answers with a pong that refreshes `last_seen`.
+    pub fn ping(&self) -> bool {
+        self.enqueue(Ok(Some(Message::ping(Vec::new()))), OutboundKind::Other)
+    }
+
+    /// Checks a received message of the given size against the limits of the config.
+    pub fn check_limits(&self, size: usize) -> Verdict {
+        let config = CONFIG.read().unwrap().limits.clone();
+        self.limiter.lock().unwrap().check(size, &config)
+    }
+
+    pub fn rejected_messages(&self) -> u64 {
+        self.limiter.lock().unwrap().rejected()
+    }
+
+    pub fn limit_warnings(&self) -> u32 {
+        self.limiter.lock().unwrap().warnings()
+    }
+
+    pub fn queued_messages(&self) -> usize {
+        self.outbound.lock().unwrap().len()
+    }
+
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages.load(Ordering::Relaxed)
+    }
+
+    /// Whether a state was dropped, so the client needs a full game state to resync.
+    pub fn take_needs_keyframe(&self) -> bool {
+        self.needs_keyframe.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn last_state_tick(&self) -> Option<u64> {
+        *self.last_state_tick.lock().unwrap()
+    }
+
+    pub fn set_last_state_tick(&self, tick: u64) {
+        *self.last_state_tick.lock().unwrap() = Some(tick);
+    }
+
+    /// Queues a message for the client. Returns false if the client is disconnected.
+    pub fn send(&self, message: &ServerMessage) -> bool {
+        let encoded = protocol::encode(message, &self.session());
+        self.enqueue(encoded, OutboundKind::of(message))
+    }
+
+    /// Queues a message shared with other clients, without serializing it again.
+    pub fn send_shared(&self, message: &SharedMessage) -> bool {
+        let encoded = message.encode(&self.session());
+        self.enqueue(encoded, OutboundKind::of(message.message()))
+    }
+
+    fn enqueue(&self, encoded: Result<Option<Message>, String>, kind: OutboundKind) -> bool {
         if !self.is_connected() {
             return false;
         }
 
-        let json = match serde_json::to_string(message) {
-            Ok(json) => json,
+        let message = match encoded {
+            Ok(Some(message)) => message,
+            Ok(None) => return true,
             Err(e) => {
                 log::error!("Failed to serialize message: {}", e);
                 return false;
             }
         };
 
-        let mut sender = self.sender.lock().await;
-        match sender.send(Message::text(json)).await {
-            Ok(_) => true,
-            Err(e) => {
-                log::error!("Failed to send message: {}", e);
-                self.connected.store(false, Ordering::Relaxed);
-                false
+        let config = CONFIG.read().unwrap().outbound.clone();
+        let mut outbound = self.outbound.lock().unwrap();
+        if config.slow_client_policy == SlowClientPolicy::DropStale && kind.is_state() {
+            let queued = outbound.len();
+            outbound.retain(|queued| !queued.kind.is_state());
+            let stale = (queued - outbound.len()) as u64;
+            if stale > 0 {
+                self.dropped_messages.fetch_add(stale, Ordering::Relaxed);
+
+                // A delta can't be applied without the states it replaces
+                if kind == OutboundKind::Delta {
+                    self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+                    self.needs_keyframe.store(true, Ordering::Relaxed);
+                    return true;
+                }
+            }
+        }
+
+        if outbound.len() >= config.queue_size {
+            match config.slow_client_policy {
+                SlowClientPolicy::DropStale => {
+                    self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+                    if kind.is_state() {
+                        self.needs_keyframe.store(true, Ordering::Relaxed);
+                    }
+                    return true;
+                }
+                SlowClientPolicy::Disconnect => {
+                    log::warn!("Disconnecting a client that can't keep up with its messages");
+                    drop(outbound);
+                    self.disconnect();
+                    return false;
+                }
             }
         }
+
+        outbound.push_back(Outbound { message, kind });
+        drop(outbound);
+        self.outbound_ready.notify_one();
+        true
+    }
+
+    /// Writes the queued messages to the socket until the client disconnects.
+    pub async fn write_messages(&self, mut sender: SplitSink<WebSocketStream<TcpStream>, Message>) {
+        while self.is_connected() {
+            let next = self.outbound.lock().unwrap().pop_front();
+            let Some(outbound) = next else {
+                if self.closing.load(Ordering::Relaxed) {
+                    break;
+                }
+                self.outbound_ready.notified().await;
+                continue;
+            };
+
+            select! {
+                result = sender.send(outbound.message) => if let Err(e) = result {
+                    log::error!("Failed to send message: {}", e);
+                    self.disconnect();
+                },
+                () = self.closed() => {}
+            }
+        }
+
+        self.outbound.lock().unwrap().clear();
+        let _ = timeout(CLOSE_TIMEOUT, sender.close()).await;
+        // Only now for closing clients, so waiting on `closed` also waits for the close frame
+        self.disconnect();
     }
 }
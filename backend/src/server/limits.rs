@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use crate::config::LimitsConfig;
+use crate::server::message::{ErrorCode, ServerMessage};
+
+/// Minimum time between two warnings sent to a client, so a flooding client gets at most one
+/// strike per second.
+const WARNING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps track of the messages received from a client to enforce `LimitsConfig`.
+pub(super) struct MessageLimiter {
+    /// Messages the client can still send right away, refilled over time.
+    tokens: f64,
+    last_refill: Instant,
+    last_warning: Option<Instant>,
+    last_rejection: Option<Instant>,
+    warnings: u32,
+    rejected: u64,
+}
+
+pub(super) enum Verdict {
+    Accept,
+    /// The message is ignored, with a warning to send if the client wasn't warned recently.
+    Reject(Option<ServerMessage>),
+    /// The client was warned too many times.
+    Disconnect,
+}
+
+impl MessageLimiter {
+    pub fn new() -> Self {
+        Self {
+            // Capped to the configured rate on the first message
+            tokens: f64::INFINITY,
+            last_refill: Instant::now(),
+            last_warning: None,
+            last_rejection: None,
+            warnings: 0,
+            rejected: 0,
+        }
+    }
+
+    pub fn warnings(&self) -> u32 {
+        self.warnings
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    pub fn check(&mut self, size: usize, config: &LimitsConfig) -> Verdict {
+        let reset = Duration::from_secs(config.warning_reset_secs);
+        if config.warning_reset_secs > 0
+            && self
+                .last_rejection
+                .is_some_and(|last| last.elapsed() >= reset)
+        {
+            self.warnings = 0;
+            self.last_rejection = None;
+        }
+
+        let warning = if config.max_message_size > 0 && size > config.max_message_size {
+            ServerMessage::error_with_context(
+                ErrorCode::MessageTooLarge,
+                "Message is too large and was ignored",
+                json!({ "size": size, "maxSize": config.max_message_size }),
+            )
+        } else if !self.take_token(config.messages_per_second) {
+            ServerMessage::error_with_context(
+                ErrorCode::RateLimited,
+                "Too many messages, this one was ignored",
+                json!({ "messagesPerSecond": config.messages_per_second }),
+            )
+        } else {
+            return Verdict::Accept;
+        };
+
+        self.rejected += 1;
+        self.last_rejection = Some(Instant::now());
+        if self
+            .last_warning
+            .is_some_and(|last| last.elapsed() < WARNING_INTERVAL)
+        {
+            return Verdict::Reject(None);
+        }
+
+        self.last_warning = Some(Instant::now());
+        self.warnings += 1;
+        if config.max_warnings > 0 && self.warnings > config.max_warnings {
+            Verdict::Disconnect
+        } else {
+            Verdict::Reject(Some(warning))
+        }
+    }
+
+    /// Token bucket allowing bursts of one second worth of messages.
+    fn take_token(&mut self, messages_per_second: u32) -> bool {
+        if messages_per_second == 0 {
+            return true;
+        }
+
+        let rate = messages_per_second as f64;
+        let now = Instant::now();
+        let refill = now.duration_since(self.last_refill).as_secs_f64() * rate;
+        self.tokens = (self.tokens + refill).min(rate);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
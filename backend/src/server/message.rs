@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::game::entities::player::Action;
-use crate::server::state::{GameState, PlayerGameState};
+use crate::game::entities::player::{Action, DeathReport, MatchSummary};
 use crate::server::ClientType;
+use crate::server::protocol::{
+    Capability, Encoding, RequestedCapability, RequestedEncoding, ServerInfo,
+};
+use crate::server::state::{GameState, GameStateDelta, MatchInfo, PlayerGameState};
 use crate::types::{Event, PlayerId, ScoreboardTeam};
 
 #[derive(Debug, Deserialize)]
@@ -17,6 +21,10 @@ pub(super) enum ClientMessage {
         client_type: ClientType,
         #[serde(rename = "teamId")]
         player_id: Option<PlayerId>,
+        protocol_version: Option<u32>,
+        #[serde(default)]
+        capabilities: Vec<RequestedCapability>,
+        encoding: Option<RequestedEncoding>,
     },
     RenameTeam {
         name: String,
@@ -29,14 +37,50 @@ pub(super) enum ClientMessage {
 #[serde(rename_all_fields = "camelCase")]
 pub enum ServerMessage {
     TickInfo {
-        state: PlayerGameState,
+        state: Box<PlayerGameState>,
+    },
+    LinkAccepted {
+        protocol_version: u32,
+        capabilities: Vec<Capability>,
+        encoding: Encoding,
+        server_info: ServerInfo,
     },
     TickInfoDead,
-    GameStart,
+    DeathReport {
+        report: DeathReport,
+    },
+    MatchSummary {
+        summary: MatchSummary,
+    },
+    GameStart {
+        info: MatchInfo,
+    },
+    /// Teams waiting for the next match, sent whenever the lobby changes.
+    Lobby {
+        teams: Vec<String>,
+        min_players: usize,
+    },
+    /// No tick will be sent until the game is resumed.
+    GamePaused {
+        reason: Option<String>,
+    },
+    GameResumed {
+        reason: Option<String>,
+    },
+    /// The server is stopping and will close the connection.
+    Shutdown,
+    /// Seconds left before the first tick of the next match.
+    Countdown {
+        seconds: u64,
+    },
     GameState {
         scoreboard: Option<Vec<ScoreboardTeam>>,
         state: GameState,
     },
+    GameStateDelta {
+        scoreboard: Option<Vec<ScoreboardTeam>>,
+        delta: GameStateDelta,
+    },
     Events {
         events: Vec<Event>,
     },
@@ -46,5 +90,59 @@ pub enum ServerMessage {
     NameConfirmation {
         name: String,
     },
-    LinkFailed,
+    RenamePending {
+        name: String,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+        context: Option<Value>,
+    },
+}
+
+impl ServerMessage {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Error {
+            code,
+            message: message.into(),
+            context: None,
+        }
+    }
+
+    pub fn error_with_context(code: ErrorCode, message: impl Into<String>, context: Value) -> Self {
+        Self::Error {
+            code,
+            message: message.into(),
+            context: Some(context),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// The `Link` message did not contain a team token.
+    MissingToken,
+    /// No team matches the token of the `Link` message.
+    UnknownToken,
+    /// The team was banned by an organizer.
+    Banned,
+    /// The message could not be parsed, the context contains the parse error.
+    BadJson,
+    /// The binary message could not be decoded as MessagePack, the context contains the error.
+    BadMessage,
+    /// The requested team name was refused, the message contains the reason.
+    InvalidRename,
+    /// The message requires the client to be linked to a team first.
+    NotLinked,
+    /// The protocol version of the `Link` message is too old, the context lists supported ones.
+    UnsupportedProtocol,
+    /// Another agent is already linked to the team.
+    TeamAlreadyLinked,
+    /// Another agent linked to the team and replaced this one.
+    LinkReplaced,
+    /// The message was ignored because it is too large, the context contains the limit.
+    MessageTooLarge,
+    /// The message was ignored because the client sends too many, the context contains the limit.
+    RateLimited,
 }
@@ -1,28 +1,43 @@
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 use futures_util::StreamExt;
 use futures_util::future::join_all;
 use serde::Deserialize;
+use serde_json::json;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::select;
 use tokio::sync::Mutex;
-use tokio_websockets::{Error, ServerBuilder, WebSocketStream};
+use tokio::time::timeout;
+use tokio_websockets::{Error, Limits, ServerBuilder, WebSocketStream};
 
 use crate::CONFIG;
-use crate::STOP_INTERRUPT;
+use crate::config::DuplicateAgentPolicy;
 use crate::game::Game;
 use crate::game::entities::player::Action;
+use crate::game::names::RenameOutcome;
 use crate::server::client::ClientState;
+use crate::server::limits::Verdict;
 use crate::server::message::ClientMessage;
-use crate::types::PlayerId;
+use crate::server::protocol::{Capability, LEGACY_PROTOCOL_VERSION, ServerInfo, SharedMessage};
+use crate::server::state::StateValues;
+use crate::types::{Event, PlayerId};
+use crate::{PAUSE_GAME, STOP_INTERRUPT};
 
 use client::Client;
-pub use message::ServerMessage;
+pub use message::{ErrorCode, ServerMessage};
 
 mod client;
+mod limits;
 mod message;
+pub mod protocol;
 pub mod state;
 
+/// Time given to the clients to receive their last messages when the server stops.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum ClientType {
@@ -33,6 +48,21 @@ pub enum ClientType {
 pub struct Server {
     pub game: Arc<Mutex<Game>>,
     clients: Mutex<Vec<Arc<Client>>>,
+    /// Dashboard states of the last ticks, to delay what dead agents can spectate.
+    spectator_states: Mutex<VecDeque<Arc<SharedMessage>>>,
+    dashboard_state: Mutex<DashboardState>,
+    /// Messages dropped by clients that have since disconnected.
+    dropped_messages: AtomicU64,
+}
+
+/// Last game state sent to dashboards, which the next delta is computed from.
+#[derive(Default)]
+struct DashboardState {
+    previous: Option<Arc<SharedMessage>>,
+    previous_tick: u64,
+    /// Values of the previous state, only kept while some dashboard uses deltas.
+    previous_values: Option<StateValues>,
+    ticks_since_keyframe: u32,
 }
 
 impl Server {
@@ -40,11 +70,84 @@ impl Server {
         Server {
             game: Arc::new(Mutex::new(Game::new())),
             clients: Mutex::new(Vec::new()),
+            spectator_states: Mutex::new(VecDeque::new()),
+            dashboard_state: Mutex::new(DashboardState::default()),
+            dropped_messages: AtomicU64::new(0),
+        }
+    }
+
+    /// Logs the connected clients and the messages dropped because they were too slow.
+    pub async fn list_clients(&self) {
+        let game = self.game.lock().await;
+        let clients = self.clients.lock().await;
+        let mut dropped = self.dropped_messages.load(Ordering::Relaxed);
+        for client in clients.iter().filter(|client| client.is_connected()) {
+            let kind = match client.state_sync() {
+                ClientState::Unregistered => "unlinked".to_owned(),
+                ClientState::Agent(id) => format!(
+                    "agent of {}",
+                    game.get_player_name(&id).map_or("?", |name| name.as_str())
+                ),
+                ClientState::Frontend(None) => "dashboard".to_owned(),
+                ClientState::Frontend(Some(id)) => format!(
+                    "dashboard of {}",
+                    game.get_player_name(&id).map_or("?", |name| name.as_str())
+                ),
+            };
+            log::info!(
+                "- {}: {} queued, {} dropped, {} rejected, {} warnings",
+                kind,
+                client.queued_messages(),
+                client.dropped_messages(),
+                client.rejected_messages(),
+                client.limit_warnings()
+            );
+            dropped += client.dropped_messages();
+        }
+        log::info!("{} messages dropped in total", dropped);
+    }
+
+    /// Logs the number of agents and dashboards linked to each team.
+    pub async fn list_connections(&self) {
+        let game = self.game.lock().await;
+        let clients = self.clients.lock().await;
+        let mut teams = HashMap::<&str, (usize, usize)>::new();
+        let mut unlinked = 0;
+        for client in clients.iter().filter(|client| client.is_connected()) {
+            let (id, is_agent) = match client.state_sync() {
+                ClientState::Agent(id) => (id, true),
+                ClientState::Frontend(Some(id)) => (id, false),
+                ClientState::Frontend(None) | ClientState::Unregistered => {
+                    unlinked += 1;
+                    continue;
+                }
+            };
+            let Some(name) = game.get_player_name(&id) else {
+                continue;
+            };
+
+            let (agents, dashboards) = teams.entry(name).or_default();
+            if is_agent {
+                *agents += 1;
+            } else {
+                *dashboards += 1;
+            }
         }
+
+        let mut teams = teams.into_iter().collect::<Vec<_>>();
+        teams.sort();
+        for (name, (agents, dashboards)) in teams {
+            log::info!("- {}: {} agents, {} dashboards", name, agents, dashboards);
+        }
+        log::info!("{} clients not linked to a team", unlinked);
     }
 
     async fn remove_disconnected_clients(&self) {
         let mut clients = self.clients.lock().await;
+        for client in clients.iter().filter(|client| !client.is_connected()) {
+            self.dropped_messages
+                .fetch_add(client.dropped_messages(), Ordering::Relaxed);
+        }
         clients.retain(|client| client.is_connected());
         let removed_count = clients.capacity() - clients.len();
         if removed_count > 0 {
@@ -53,17 +156,74 @@ impl Server {
         }
     }
 
+    /// Shows a team as disconnected in the scoreboard once its last agent is gone.
+    async fn agent_unlinked(&self, id: &PlayerId) {
+        // The game stays locked so an agent linking meanwhile is either seen or marks the team
+        // connected after this
+        let mut game = self.game.lock().await;
+        let clients = self.clients.lock().await.clone();
+        for client in clients {
+            if client.is_connected() && *client.state().await == ClientState::Agent(id.clone()) {
+                return;
+            }
+        }
+        game.set_player_connected(id, false);
+    }
+
+    /// Pings every client periodically and disconnects the ones that stopped answering, like
+    /// laptops that went to sleep without closing their connection.
+    fn start_heartbeat(self: &Arc<Self>) {
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                let (interval, timeout) = {
+                    let config = CONFIG.read().unwrap();
+                    (
+                        Duration::from_secs(config.heartbeat_interval_secs),
+                        Duration::from_secs(config.heartbeat_timeout_secs),
+                    )
+                };
+
+                select! {
+                    () = tokio::time::sleep(interval) => {}
+                    () = STOP_INTERRUPT.notified() => break,
+                }
+
+                let clients = server.clients.lock().await.clone();
+                for client in clients {
+                    if client.idle_time() > timeout {
+                        log::info!("Disconnecting a client that stopped answering pings");
+                        client.disconnect();
+                    } else {
+                        client.ping();
+                    }
+                }
+
+                server.remove_disconnected_clients().await;
+            }
+        });
+    }
+
     pub async fn listen(self: &Arc<Self>) -> Result<(), Error> {
-        let config = CONFIG.read().unwrap();
-        let listener = TcpListener::bind((config.address, config.port)).await?;
-        drop(config);
+        let address = {
+            let config = CONFIG.read().unwrap();
+            (config.address, config.port)
+        };
+        let listener = TcpListener::bind(address).await?;
 
         let addr = listener.local_addr().unwrap();
         log::info!("Server listening for connections at {}", addr);
 
+        self.start_heartbeat();
+
         let server = Arc::clone(self);
         tokio::spawn(async move {
-            let handshake = ServerBuilder::new();
+            // Frames well above the limit close the connection instead of being buffered
+            let max_message_size = CONFIG.read().unwrap().limits.max_message_size;
+            let handshake = ServerBuilder::new().limits(
+                Limits::default()
+                    .max_payload_len((max_message_size > 0).then(|| max_message_size * 4)),
+            );
 
             while let Some(stream) = select! {
                 Ok((stream, _)) = listener.accept() => Some(stream),
@@ -86,91 +246,332 @@ impl Server {
     }
 
     pub fn send_game_state(self: &Arc<Self>) {
+        self.broadcast_game_state(true);
+    }
+
+    /// Sends the game state to the dashboards only, to show that the game was paused or resumed
+    /// without making agents believe a tick happened.
+    pub fn send_dashboard_state(self: &Arc<Self>) {
+        self.broadcast_game_state(false);
+    }
+
+    fn broadcast_game_state(self: &Arc<Self>, include_agents: bool) {
         log::debug!("Sending game state to players");
 
         let server = Arc::clone(self);
         tokio::spawn(async move {
             // Get game state once and reuse
-            let (game_state_message, player_states) = {
+            let (tick, game_state_message, delta_message, player_states) = {
                 let game = server.game.lock().await;
-                let game_state = game.get_game_state();
-                let mut player_states = Vec::new();
-
-                // Pre-collect all player states to minimize lock time
-                let clients_guard = server.clients.lock().await;
-                for client in clients_guard.iter() {
-                    if let ClientState::Agent(ref id) = *client.state().await {
-                        let state = game.get_player_game_state(id);
-                        player_states.push((
-                            client.clone(),
-                            match state {
-                                Some(state) => ServerMessage::TickInfo { state },
-                                None => ServerMessage::TickInfoDead,
-                            },
-                        ));
-                    }
-                }
+                let tick = game.current_tick();
+                let game_state = Arc::new(SharedMessage::new(game.get_game_state()));
+                let wants_delta = server
+                    .frontend_clients()
+                    .await
+                    .iter()
+                    .any(|client| client.session().has(Capability::DeltaState));
+                let delta_state = server
+                    .record_dashboard_state(&game_state, tick, wants_delta)
+                    .await;
+                let player_states = if include_agents {
+                    server.agent_states(&game, &game_state).await
+                } else {
+                    Vec::new()
+                };
 
-                (game_state, player_states)
+                (tick, game_state, delta_state, player_states)
             };
 
-            // Send game state to frontend clients
-            server.send_message_frontend(game_state_message);
+            // Send game state to frontend clients, as a delta to those who asked for it and
+            // received the state it starts from
+            for client in server.frontend_clients().await {
+                let needs_keyframe = client.take_needs_keyframe();
+                match &delta_message {
+                    Some((from_tick, delta))
+                        if client.session().has(Capability::DeltaState)
+                            && !needs_keyframe
+                            && client.last_state_tick() == Some(*from_tick) =>
+                    {
+                        client.send_shared(delta)
+                    }
+                    _ => client.send_shared(&game_state_message),
+                };
+                client.set_last_state_tick(tick);
+            }
 
             // Send messages to agents with their specific state
-            join_all(
-                player_states
-                    .iter()
-                    .map(|(client, state)| client.send(&state)),
-            )
-            .await;
+            for (client, state) in &player_states {
+                client.send_shared(state);
+            }
 
             // Clean up disconnected clients
             server.remove_disconnected_clients().await;
         });
     }
 
-    pub fn send_message_agent(self: &Arc<Self>, message: ServerMessage) {
-        log::debug!("Sending message to agents...");
+    /// Game state of every agent, or the dashboard state dead agents may spectate.
+    async fn agent_states(
+        &self,
+        game: &Game,
+        game_state: &Arc<SharedMessage>,
+    ) -> Vec<(Arc<Client>, Arc<SharedMessage>)> {
+        let spectator_state = self.record_spectator_state(game_state).await;
+        let dead_state = Arc::new(SharedMessage::new(ServerMessage::TickInfoDead));
+        let mut player_states = Vec::new();
+
+        // Pre-collect all player states to minimize lock time
+        let clients_guard = self.clients.lock().await;
+        for client in clients_guard.iter() {
+            if let ClientState::Agent(ref id) = *client.state().await {
+                let state = match game.get_player_game_state(id) {
+                    Some(state) => Arc::new(SharedMessage::new(ServerMessage::TickInfo {
+                        state: Box::new(state),
+                    })),
+                    None if client.session().has(Capability::Spectate) => spectator_state.clone(),
+                    None => dead_state.clone(),
+                };
+                player_states.push((client.clone(), state));
+            }
+        }
+
+        player_states
+    }
+
+    /// Keeps the recent dashboard states and returns the one dead agents may spectate.
+    async fn record_spectator_state(&self, game_state: &Arc<SharedMessage>) -> Arc<SharedMessage> {
+        let delay = CONFIG.read().unwrap().spectator_delay_ticks as usize;
+        let mut states = self.spectator_states.lock().await;
+        states.push_back(game_state.clone());
+        while states.len() > delay + 1 {
+            states.pop_front();
+        }
+        states.front().unwrap().clone()
+    }
 
+    /// Keeps the game state sent to dashboards and returns the delta from the previous one with
+    /// the tick it starts from, or `None` when a full keyframe should be sent instead.
+    async fn record_dashboard_state(
+        &self,
+        game_state: &Arc<SharedMessage>,
+        tick: u64,
+        wants_delta: bool,
+    ) -> Option<(u64, Arc<SharedMessage>)> {
+        let interval = CONFIG.read().unwrap().keyframe_interval_ticks;
+        let mut dashboard = self.dashboard_state.lock().await;
+        let previous = dashboard.previous.replace(game_state.clone());
+        let previous_tick = std::mem::replace(&mut dashboard.previous_tick, tick);
+        let previous_values = dashboard.previous_values.take();
+
+        // Comparing states takes time every tick, so only when a dashboard can use the delta
+        let delta = match (
+            previous.as_deref().map(SharedMessage::message),
+            game_state.message(),
+        ) {
+            (
+                Some(ServerMessage::GameState {
+                    state: previous, ..
+                }),
+                ServerMessage::GameState { scoreboard, state },
+            ) if wants_delta => {
+                let values = state.values();
+                let delta = if dashboard.ticks_since_keyframe + 1 < interval {
+                    let previous_values = previous_values.unwrap_or_else(|| previous.values());
+                    state.diff(&values, previous, &previous_values)
+                } else {
+                    None
+                };
+                dashboard.previous_values = Some(values);
+                delta.map(|delta| ServerMessage::GameStateDelta {
+                    scoreboard: scoreboard.clone(),
+                    delta,
+                })
+            }
+            _ => None,
+        };
+
+        match delta {
+            Some(delta) => {
+                dashboard.ticks_since_keyframe += 1;
+                Some((previous_tick, Arc::new(SharedMessage::new(delta))))
+            }
+            None => {
+                dashboard.ticks_since_keyframe = 0;
+                None
+            }
+        }
+    }
+
+    /// Sends the rules and map metadata of the current match to every agent, before the first
+    /// tick of the match is sent.
+    pub async fn send_game_start(&self) {
+        let info = self.game.lock().await.get_match_info();
+        let message = SharedMessage::new(ServerMessage::GameStart { info });
+        self.spectator_states.lock().await.clear();
+        // The map changed, start the new match with a keyframe
+        self.dashboard_state.lock().await.previous = None;
+
+        for client in self.agent_clients().await {
+            client.send_shared(&message);
+        }
+    }
+
+    /// Sends a message to the agents linked to a specific team.
+    pub async fn send_message_agent(&self, id: &PlayerId, message: impl Into<SharedMessage>) {
+        let message = message.into();
+        for client in self.agent_clients().await {
+            if client.state_sync() == ClientState::Agent(id.clone()) {
+                client.send_shared(&message);
+            }
+        }
+    }
+
+    async fn agent_clients(&self) -> Vec<Arc<Client>> {
+        let clients = self.clients.lock().await;
+        let mut agent_clients = Vec::new();
+        for client in clients.iter() {
+            if matches!(client.state_sync(), ClientState::Agent(_)) {
+                agent_clients.push(client.clone());
+            }
+        }
+        agent_clients
+    }
+
+    /// Sends every event to the dashboards, and to each agent the public events and the ones its
+    /// team is involved in.
+    pub fn send_events(self: &Arc<Self>, events: Vec<Event>) {
         let server = Arc::clone(self);
         tokio::spawn(async move {
-            let clients = server.clients.lock().await;
-            let mut agent_clients = Vec::new();
-            for client in clients.iter() {
-                if matches!(client.state_sync(), ClientState::Agent(_)) {
-                    agent_clients.push(client.clone());
-                }
+            let agent_ids = server
+                .agent_clients()
+                .await
+                .into_iter()
+                // Legacy starter kits don't expect events
+                .filter(|client| client.session().protocol_version > LEGACY_PROTOCOL_VERSION)
+                .filter_map(|client| match client.state_sync() {
+                    ClientState::Agent(id) => Some((client, id)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            // Agents only involved in public events share the same message
+            let public_events = events
+                .iter()
+                .filter(|event| event.is_public())
+                .cloned()
+                .collect::<Vec<_>>();
+            let public_message = (!public_events.is_empty()).then(|| {
+                Arc::new(SharedMessage::new(ServerMessage::Events {
+                    events: public_events,
+                }))
+            });
+
+            let agent_messages = agent_ids
+                .into_iter()
+                .filter_map(|(client, id)| {
+                    let involved = events
+                        .iter()
+                        .any(|event| !event.is_public() && event.involves(&id));
+                    let message = match (involved, &public_message) {
+                        (false, Some(message)) => message.clone(),
+                        (false, None) => return None,
+                        (true, _) => {
+                            let events = events
+                                .iter()
+                                .filter(|event| event.is_public() || event.involves(&id))
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            Arc::new(SharedMessage::new(ServerMessage::Events { events }))
+                        }
+                    };
+                    Some((client, message))
+                })
+                .collect::<Vec<_>>();
+
+            server.send_message_frontend(ServerMessage::Events { events });
+            for (client, message) in &agent_messages {
+                client.send_shared(message);
             }
-            drop(clients);
-
-            // Send messages without holding the clients lock
-            join_all(agent_clients.iter().map(|client| client.send(&message))).await;
         });
     }
 
-    pub fn send_message_frontend(self: &Arc<Self>, message: ServerMessage) {
+    pub fn send_message_frontend(self: &Arc<Self>, message: impl Into<SharedMessage>) {
         log::debug!("Sending message to frontends...");
 
+        let message = message.into();
         let server = Arc::clone(self);
         tokio::spawn(async move {
-            let clients = server.clients.lock().await;
-            let mut frontend_clients = Vec::new();
-            for client in clients.iter() {
-                if matches!(client.state_sync(), ClientState::Frontend(_)) {
-                    frontend_clients.push(client.clone());
-                }
+            for client in server.frontend_clients().await {
+                client.send_shared(&message);
             }
-            drop(clients);
+        });
+    }
 
-            // Send messages without holding the clients lock
-            join_all(frontend_clients.iter().map(|client| client.send(&message))).await;
+    async fn frontend_clients(&self) -> Vec<Arc<Client>> {
+        let clients = self.clients.lock().await;
+        let mut frontend_clients = Vec::new();
+        for client in clients.iter() {
+            if matches!(client.state_sync(), ClientState::Frontend(_)) {
+                frontend_clients.push(client.clone());
+            }
+        }
+        frontend_clients
+    }
+
+    /// Sends a message to every agent and dashboard.
+    pub async fn send_message_all(&self, message: impl Into<SharedMessage>) {
+        let message = message.into();
+        for client in self.agent_clients().await {
+            client.send_shared(&message);
+        }
+        for client in self.frontend_clients().await {
+            client.send_shared(&message);
+        }
+    }
+
+    /// Tells every client that the server is stopping, then closes the connections once their
+    /// queued messages are written.
+    pub async fn shutdown(&self) {
+        log::info!("Closing client connections...");
+        let message = SharedMessage::new(ServerMessage::Shutdown);
+        let clients = self.clients.lock().await.clone();
+        for client in &clients {
+            client.send_shared(&message);
+            client.close();
+        }
+
+        if timeout(
+            SHUTDOWN_TIMEOUT,
+            join_all(clients.iter().map(|c| c.closed())),
+        )
+        .await
+        .is_err()
+        {
+            log::warn!("Some clients did not receive every message before the shutdown");
+        }
+    }
+
+    /// Sends a message to the dashboards linked to a specific team.
+    pub fn send_message_dashboard(self: &Arc<Self>, id: PlayerId, message: ServerMessage) {
+        log::debug!("Sending message to the dashboards of {}...", *id);
+
+        let message = SharedMessage::new(message);
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            for client in server.frontend_clients().await {
+                if client.state_sync() == ClientState::Frontend(Some(id.clone())) {
+                    client.send_shared(&message);
+                }
+            }
         });
     }
 
     async fn handle_connection(self: &Arc<Self>, stream: WebSocketStream<TcpStream>) {
         let (sender, mut receiver) = stream.split();
-        let client = Arc::new(Client::new(sender));
+        let client = Arc::new(Client::new());
+        tokio::spawn({
+            let client = Arc::clone(&client);
+            async move { client.write_messages(sender).await }
+        });
 
         {
             let mut clients = self.clients.lock().await;
@@ -179,20 +580,49 @@ impl Server {
 
         let server = Arc::clone(self);
         tokio::spawn(async move {
-            while let Some(Ok(msg)) = receiver.next().await {
+            while let Some(msg) = select! {
+                msg = receiver.next() => msg,
+                () = client.closed() => None,
+            } {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        log::debug!("Failed to read from a client: {}", e);
+                        break;
+                    }
+                };
+
+                client.mark_seen();
                 if msg.is_close() {
-                    return;
+                    break;
                 }
-
-                let Some(msg_text) = msg.as_text() else {
-                    log::warn!("Received non-text message: {:?}", msg);
+                if msg.is_ping() || msg.is_pong() {
                     continue;
-                };
+                }
 
-                let client_message = match serde_json::from_str::<ClientMessage>(msg_text) {
-                    Ok(msg) => msg,
-                    Err(e) => {
-                        log::trace!("Failed to parse client message: {}", e);
+                match client.check_limits(msg.as_payload().len()) {
+                    Verdict::Accept => {}
+                    Verdict::Reject(warning) => {
+                        if let Some(warning) = warning {
+                            client.send(&warning);
+                        }
+                        continue;
+                    }
+                    Verdict::Disconnect => {
+                        log::warn!("Disconnecting a client that kept exceeding the message limits");
+                        break;
+                    }
+                }
+
+                let client_message = match protocol::decode(&msg) {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(error)) => {
+                        log::trace!("Failed to parse client message: {:?}", error);
+                        client.send(&error);
+                        continue;
+                    }
+                    None => {
+                        log::warn!("Received message without data: {:?}", msg);
                         continue;
                     }
                 };
@@ -203,11 +633,27 @@ impl Server {
                     ClientMessage::Link {
                         client_type,
                         player_id,
-                    } => {
-                        server
-                            .handle_link_message(&client, client_type, player_id)
-                            .await;
-                    }
+                        protocol_version,
+                        capabilities,
+                        encoding,
+                    } => match protocol::negotiate(protocol_version, capabilities, encoding) {
+                        Ok(session) => {
+                            client.set_session(session);
+                            let previous = client.state().await.clone();
+                            server
+                                .handle_link_message(&client, client_type, player_id)
+                                .await;
+                            // An agent linking to another team or as a dashboard leaves its team
+                            if let ClientState::Agent(id) = previous
+                                && *client.state().await != ClientState::Agent(id.clone())
+                            {
+                                server.agent_unlinked(&id).await;
+                            }
+                        }
+                        Err(requested) => {
+                            client.send(&protocol::unsupported_protocol(requested));
+                        }
+                    },
                     ClientMessage::Action { action } => {
                         server.handle_action_message(&client, action).await;
                     }
@@ -216,6 +662,13 @@ impl Server {
                     }
                 }
             }
+
+            client.disconnect();
+            let state = client.state().await.clone();
+            if let ClientState::Agent(id) = state {
+                server.agent_unlinked(&id).await;
+            }
+            server.remove_disconnected_clients().await;
         });
     }
 
@@ -228,71 +681,200 @@ impl Server {
         match client_type {
             ClientType::Agent => {
                 let Some(player_id) = player_id else {
-                    client.send(&ServerMessage::LinkFailed).await;
+                    client.send(&ServerMessage::error(
+                        ErrorCode::MissingToken,
+                        "Agents must link with a team token",
+                    ));
                     return;
                 };
 
-                let has_player = self.game.lock().await.get_player_name(&player_id).is_some();
-                if has_player {
-                    client.set_state(ClientState::Agent(player_id)).await;
-                } else {
-                    client.send(&ServerMessage::LinkFailed).await;
+                // The game stays locked until the client is linked, so two agents linking at the
+                // same time can't both miss each other
+                let mut game = self.game.lock().await;
+                if let Err(code) = Self::check_team(&game, &player_id) {
+                    drop(game);
+                    client.send(&Self::link_error(code, &player_id));
+                    return;
+                }
+
+                let linked_agents = self
+                    .agent_clients()
+                    .await
+                    .into_iter()
+                    .filter(|other| !Arc::ptr_eq(other, client))
+                    .filter(|other| other.state_sync() == ClientState::Agent(player_id.clone()))
+                    .collect::<Vec<_>>();
+                let policy = CONFIG.read().unwrap().duplicate_agent_policy;
+                let replaced_agents = match policy {
+                    DuplicateAgentPolicy::Reject if !linked_agents.is_empty() => {
+                        drop(game);
+                        client.send(&ServerMessage::error(
+                            ErrorCode::TeamAlreadyLinked,
+                            "Another agent is already linked to this team",
+                        ));
+                        return;
+                    }
+                    DuplicateAgentPolicy::Replace => linked_agents,
+                    _ => Vec::new(),
+                };
+
+                game.set_player_connected(&player_id, true);
+                client.set_state(ClientState::Agent(player_id)).await;
+                drop(game);
+
+                // Kicked once the game is unlocked, as they may be waiting for it with their
+                // state locked
+                for other in replaced_agents {
+                    other.set_state(ClientState::Unregistered).await;
+                    other.send(&ServerMessage::error(
+                        ErrorCode::LinkReplaced,
+                        "Another agent linked to this team",
+                    ));
+                    other.close();
+                }
+                client.send(&Self::link_accepted(client));
+
+                // Agents linking mid-game still need the rules of the current match
+                let game = self.game.lock().await;
+                client.send(&ServerMessage::GameStart {
+                    info: game.get_match_info(),
+                });
+                if !game.is_match_running() {
+                    client.send(&game.get_lobby());
+                }
+                // Otherwise the agent waits for a tick without knowing why
+                if PAUSE_GAME.load(Ordering::Relaxed) {
+                    client.send(&ServerMessage::GamePaused { reason: None });
                 }
             }
             ClientType::Dashboard => {
+                client.send(&Self::link_accepted(client));
+
                 // Send current game state immediately
-                let game_state = self.game.lock().await.get_game_state();
-                client.send(&game_state).await;
+                let game = self.game.lock().await;
+                client.send(&game.get_game_state());
+                client.set_last_state_tick(game.current_tick());
+                if !game.is_match_running() {
+                    client.send(&game.get_lobby());
+                }
+                drop(game);
 
                 let Some(player_id) = player_id else {
                     client.set_state(ClientState::Frontend(None)).await;
                     return;
                 };
 
-                let player_name = self.game.lock().await.get_player_name(&player_id).cloned();
-                match player_name {
-                    Some(name) => {
+                let result = Self::check_team(&*self.game.lock().await, &player_id);
+                match result {
+                    Ok(name) => {
                         client
                             .set_state(ClientState::Frontend(Some(player_id)))
                             .await;
-                        client.send(&ServerMessage::NameConfirmation { name }).await;
+                        client.send(&ServerMessage::NameConfirmation { name });
                     }
-                    None => {
+                    Err(code) => {
                         client.set_state(ClientState::Frontend(None)).await;
-                        client.send(&ServerMessage::LinkFailed).await;
+                        client.send(&Self::link_error(code, &player_id));
                     }
                 }
             }
         }
     }
 
-    async fn handle_action_message(self: &Arc<Self>, client: &Arc<Client>, action: Action) {
-        if let ClientState::Agent(ref id) = *client.state().await {
-            self.game.lock().await.set_player_action(id, action);
+    fn link_accepted(client: &Client) -> ServerMessage {
+        let session = client.session();
+        ServerMessage::LinkAccepted {
+            protocol_version: session.protocol_version,
+            capabilities: session.capabilities,
+            encoding: session.encoding,
+            server_info: ServerInfo::new(),
         }
     }
 
-    async fn handle_rename_message(self: &Arc<Self>, client: &Arc<Client>, new_name: String) {
-        if let ClientState::Frontend(ref player_id_opt) = *client.state().await {
-            match player_id_opt.as_ref() {
-                Some(player_id) => {
-                    let rename_success = self
-                        .game
-                        .lock()
-                        .await
-                        .rename_player(player_id, new_name.clone());
-                    if rename_success {
-                        client
-                            .send(&ServerMessage::NameConfirmation { name: new_name })
-                            .await;
-                    } else {
-                        client.send(&ServerMessage::LinkFailed).await;
-                    }
-                }
-                None => {
-                    client.send(&ServerMessage::LinkFailed).await;
-                }
+    /// Returns the name of the team, or the reason why a client can't link to it.
+    fn check_team(game: &Game, player_id: &PlayerId) -> Result<String, ErrorCode> {
+        match game.get_player_name(player_id) {
+            None => Err(ErrorCode::UnknownToken),
+            Some(_) if game.is_player_banned(player_id) => Err(ErrorCode::Banned),
+            Some(name) => Ok(name.clone()),
+        }
+    }
+
+    fn link_error(code: ErrorCode, player_id: &PlayerId) -> ServerMessage {
+        match code {
+            ErrorCode::Banned => {
+                ServerMessage::error(code, "This team was banned by the organizers")
             }
+            _ => ServerMessage::error_with_context(
+                code,
+                "No team matches this token",
+                json!({ "teamId": player_id }),
+            ),
         }
     }
+
+    /// Unlinks every client of a banned team.
+    pub fn unlink_team(self: &Arc<Self>, id: PlayerId) {
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            let clients = server.clients.lock().await.clone();
+            for client in clients {
+                let linked = match &*client.state().await {
+                    ClientState::Agent(client_id) => *client_id == id,
+                    ClientState::Frontend(client_id) => client_id.as_ref() == Some(&id),
+                    ClientState::Unregistered => false,
+                };
+                if linked {
+                    client.set_state(ClientState::Unregistered).await;
+                    client.send(&Self::link_error(ErrorCode::Banned, &id));
+                }
+            }
+
+            server.agent_unlinked(&id).await;
+            server.remove_disconnected_clients().await;
+        });
+    }
+
+    async fn handle_action_message(self: &Arc<Self>, client: &Arc<Client>, action: Action) {
+        // The state is unlocked before locking the game, which is locked before the states when
+        // sending game states
+        let ClientState::Agent(id) = client.state().await.clone() else {
+            // Legacy kits never got an answer to these and would take it for a failed link
+            if client.session().protocol_version > LEGACY_PROTOCOL_VERSION {
+                client.send(&ServerMessage::error(
+                    ErrorCode::NotLinked,
+                    "Link as an agent before sending actions",
+                ));
+            }
+            return;
+        };
+
+        self.game.lock().await.set_player_action(&id, action);
+    }
+
+    async fn handle_rename_message(self: &Arc<Self>, client: &Arc<Client>, new_name: String) {
+        let ClientState::Frontend(Some(player_id)) = client.state().await.clone() else {
+            client.send(&ServerMessage::error(
+                ErrorCode::NotLinked,
+                "Link a dashboard to your team before renaming it",
+            ));
+            return;
+        };
+
+        let result = self
+            .game
+            .lock()
+            .await
+            .rename_player(&player_id, new_name.clone());
+        let message = match result {
+            Ok(RenameOutcome::Renamed(name)) => ServerMessage::NameConfirmation { name },
+            Ok(RenameOutcome::Pending(name)) => ServerMessage::RenamePending { name },
+            Err(e) => ServerMessage::error_with_context(
+                ErrorCode::InvalidRename,
+                e.to_string(),
+                json!({ "name": new_name }),
+            ),
+        };
+        client.send(&message);
+    }
 }
@@ -0,0 +1,292 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio_websockets::Message;
+
+use crate::server::message::{ClientMessage, ErrorCode, ServerMessage};
+
+/// Version of the message shapes of `message.rs` and `state.rs`. Bump it whenever they change
+/// in a way older clients can't handle, and teach `legacy_message` how to serve the old shape.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Version assumed for clients that don't send one, i.e. the starter kits of the competition.
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features a client can opt into when linking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    /// Dead agents receive the dashboard game state instead of `TickInfoDead`.
+    Spectate,
+    /// Dashboards receive `GameStateDelta` between periodic full `GameState` keyframes.
+    DeltaState,
+}
+
+impl Capability {
+    pub fn all() -> [Capability; 2] {
+        [Capability::Spectate, Capability::DeltaState]
+    }
+}
+
+/// Capabilities requested by clients, kept even when unknown so newer clients can still link.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(super) enum RequestedCapability {
+    Known(Capability),
+    Unknown(String),
+}
+
+/// Format of the messages sent to a client. Clients can send messages in any of them, as text
+/// frames for JSON and binary frames for MessagePack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn all() -> [Encoding; 2] {
+        [Encoding::Json, Encoding::MessagePack]
+    }
+}
+
+/// Encoding requested by clients, falling back to JSON when unknown.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(super) enum RequestedEncoding {
+    Known(Encoding),
+    Unknown(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub protocol_version: u32,
+    pub capabilities: Vec<Capability>,
+    pub encoding: Encoding,
+}
+
+impl Session {
+    pub fn has(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// Session of a client that didn't link yet, which may be a starter kit expecting the legacy
+/// messages.
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            protocol_version: LEGACY_PROTOCOL_VERSION,
+            capabilities: Vec::new(),
+            encoding: Encoding::Json,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub min_protocol_version: u32,
+    pub max_protocol_version: u32,
+    pub capabilities: Vec<Capability>,
+    pub encodings: Vec<Encoding>,
+}
+
+impl ServerInfo {
+    pub fn new() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            min_protocol_version: LEGACY_PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION,
+            capabilities: Capability::all().to_vec(),
+            encodings: Encoding::all().to_vec(),
+        }
+    }
+}
+
+/// Picks the highest protocol version both sides understand, the supported subset of the
+/// requested capabilities and the requested encoding. Fails with the requested version if it is
+/// too old.
+pub(super) fn negotiate(
+    protocol_version: Option<u32>,
+    capabilities: Vec<RequestedCapability>,
+    encoding: Option<RequestedEncoding>,
+) -> Result<Session, u32> {
+    let requested = protocol_version.unwrap_or(LEGACY_PROTOCOL_VERSION);
+    if requested < LEGACY_PROTOCOL_VERSION {
+        return Err(requested);
+    }
+
+    let encoding = match encoding {
+        None => Encoding::Json,
+        Some(RequestedEncoding::Known(encoding)) => encoding,
+        Some(RequestedEncoding::Unknown(name)) => {
+            log::debug!("Client requested unknown encoding {}", name);
+            Encoding::Json
+        }
+    };
+
+    let mut session = Session {
+        protocol_version: requested.min(PROTOCOL_VERSION),
+        capabilities: Vec::new(),
+        encoding,
+    };
+    for capability in capabilities {
+        match capability {
+            RequestedCapability::Known(capability) if !session.has(capability) => {
+                session.capabilities.push(capability);
+            }
+            RequestedCapability::Known(_) => {}
+            RequestedCapability::Unknown(name) => {
+                log::debug!("Client requested unknown capability {}", name);
+            }
+        }
+    }
+
+    Ok(session)
+}
+
+pub(super) fn unsupported_protocol(requested: u32) -> ServerMessage {
+    ServerMessage::error_with_context(
+        ErrorCode::UnsupportedProtocol,
+        "Protocol version is not supported",
+        json!({
+            "requested": requested,
+            "minProtocolVersion": LEGACY_PROTOCOL_VERSION,
+            "maxProtocolVersion": PROTOCOL_VERSION,
+        }),
+    )
+}
+
+/// Shape of a message for clients of the legacy protocol.
+enum LegacyMessage {
+    Unchanged,
+    Replaced(Value),
+    Skipped,
+}
+
+fn legacy_message(message: &ServerMessage) -> LegacyMessage {
+    match message {
+        ServerMessage::LinkAccepted { .. }
+        | ServerMessage::RenamePending { .. }
+        | ServerMessage::DeathReport { .. }
+        | ServerMessage::MatchSummary { .. }
+        | ServerMessage::Lobby { .. }
+        | ServerMessage::Countdown { .. }
+        | ServerMessage::GamePaused { .. }
+        | ServerMessage::GameResumed { .. }
+        | ServerMessage::Shutdown
+        | ServerMessage::GameStateDelta { .. } => LegacyMessage::Skipped,
+        ServerMessage::Error { code, .. } => match code {
+            ErrorCode::BadJson
+            | ErrorCode::BadMessage
+            | ErrorCode::UnsupportedProtocol
+            | ErrorCode::MessageTooLarge
+            | ErrorCode::RateLimited => LegacyMessage::Skipped,
+            _ => LegacyMessage::Replaced(json!({ "type": "linkFailed" })),
+        },
+        _ => LegacyMessage::Unchanged,
+    }
+}
+
+/// Serializes a message in the shape expected by the protocol version of the session and in its
+/// encoding, or returns `None` if clients of that version don't know about this message.
+pub fn encode(message: &ServerMessage, session: &Session) -> Result<Option<Message>, String> {
+    if session.protocol_version >= PROTOCOL_VERSION {
+        return encode_as(message, session.encoding).map(Some);
+    }
+
+    match legacy_message(message) {
+        LegacyMessage::Unchanged => encode_as(message, session.encoding).map(Some),
+        LegacyMessage::Replaced(value) => encode_as(&value, session.encoding).map(Some),
+        LegacyMessage::Skipped => Ok(None),
+    }
+}
+
+/// Protocol version and encoding of a client, which determine the bytes it receives.
+type WireFormat = (u32, Encoding);
+
+/// A message sent to many clients, encoded once per wire format and shared between them.
+pub struct SharedMessage {
+    message: ServerMessage,
+    encoded: Mutex<Vec<(WireFormat, Option<Message>)>>,
+}
+
+impl SharedMessage {
+    pub fn new(message: ServerMessage) -> Self {
+        Self {
+            message,
+            encoded: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn message(&self) -> &ServerMessage {
+        &self.message
+    }
+
+    /// Same as `encode`, reusing the bytes of a previous client with the same wire format.
+    pub fn encode(&self, session: &Session) -> Result<Option<Message>, String> {
+        let format = (session.protocol_version, session.encoding);
+        let mut encoded = self.encoded.lock().unwrap();
+        if let Some((_, message)) = encoded.iter().find(|(f, _)| *f == format) {
+            return Ok(message.clone());
+        }
+
+        let message = encode(&self.message, session)?;
+        encoded.push((format, message.clone()));
+        Ok(message)
+    }
+}
+
+impl From<ServerMessage> for SharedMessage {
+    fn from(message: ServerMessage) -> Self {
+        Self::new(message)
+    }
+}
+
+fn encode_as(message: &impl Serialize, encoding: Encoding) -> Result<Message, String> {
+    match encoding {
+        Encoding::Json => serde_json::to_string(message)
+            .map(Message::text)
+            .map_err(|e| e.to_string()),
+        Encoding::MessagePack => rmp_serde::to_vec_named(message)
+            .map(Message::binary)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Parses a message received from a client, as JSON for text frames and MessagePack for binary
+/// frames. Returns `None` for frames that don't carry a message.
+pub(super) fn decode(message: &Message) -> Option<Result<ClientMessage, ServerMessage>> {
+    if let Some(text) = message.as_text() {
+        return Some(serde_json::from_str(text).map_err(|e| {
+            ServerMessage::error_with_context(
+                ErrorCode::BadJson,
+                "Failed to parse message",
+                json!({
+                    "error": e.to_string(),
+                    "line": e.line(),
+                    "column": e.column(),
+                }),
+            )
+        }));
+    }
+
+    if message.is_binary() {
+        return Some(rmp_serde::from_slice(message.as_payload()).map_err(|e| {
+            ServerMessage::error_with_context(
+                ErrorCode::BadMessage,
+                "Failed to decode message",
+                json!({ "error": e.to_string() }),
+            )
+        }));
+    }
+
+    None
+}
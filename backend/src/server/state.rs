@@ -1,12 +1,19 @@
+use std::collections::HashMap;
+
 use serde::Serialize;
+use serde_json::Value;
 
+use crate::config::ScoreConfig;
 use crate::game::entities::Projectile;
 use crate::game::entities::objects::Object;
 use crate::game::entities::player::Player;
-use crate::types::Position;
+use crate::game::items::Item;
+use crate::types::{FirewallPattern, Position};
 
 #[derive(Debug, Serialize)]
 pub struct GameState {
+    /// Whether an organizer paused the game, so no tick will come until it is resumed.
+    pub paused: bool,
     pub players: Vec<Player>,
     pub stats: GameStats,
     pub ground: PlayerView,
@@ -32,7 +39,7 @@ pub struct PlayerView {
     pub offset: Position,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(rename_all_fields = "camelCase")]
 pub enum PlayerViewCell {
@@ -47,9 +54,197 @@ pub enum PlayerViewCell {
     Empty,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GameStats {
     pub alive_count: usize,
     pub dead_count: usize,
 }
+
+/// Changes of the dashboard game state since the previous one, players are identified by name
+/// and objects by position and type, as a trap can be under a wall.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameStateDelta {
+    pub paused: bool,
+    pub players: Vec<Player>,
+    pub removed_players: Vec<String>,
+    pub stats: GameStats,
+    pub ground: Vec<GroundChange>,
+    pub objects: Vec<Object>,
+    pub removed_objects: Vec<ObjectKey>,
+    pub projectiles: Vec<Projectile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ObjectKey {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub position: Position,
+}
+
+impl From<&Object> for ObjectKey {
+    fn from(object: &Object) -> Self {
+        Self {
+            kind: object.type_name(),
+            position: object.position().clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroundChange {
+    /// Index of the cell in `PlayerView::data`.
+    pub index: usize,
+    pub cell: PlayerViewCell,
+}
+
+/// What dashboards see of the players and objects of a state, kept to compare the next one.
+#[derive(Default)]
+pub struct StateValues {
+    players: HashMap<String, Value>,
+    objects: HashMap<ObjectKey, Value>,
+}
+
+impl GameState {
+    pub fn values(&self) -> StateValues {
+        StateValues {
+            players: self
+                .players
+                .iter()
+                .map(|p| (p.name().clone(), to_value(p)))
+                .collect(),
+            objects: self
+                .objects
+                .iter()
+                .map(|o| (ObjectKey::from(o), to_value(o)))
+                .collect(),
+        }
+    }
+
+    /// Changes from `previous` to this state, or `None` if the map size changed. The values are
+    /// the ones of this state and of `previous`.
+    pub fn diff(
+        &self,
+        values: &StateValues,
+        previous: &GameState,
+        previous_values: &StateValues,
+    ) -> Option<GameStateDelta> {
+        if self.ground.width != previous.ground.width
+            || self.ground.height != previous.ground.height
+        {
+            return None;
+        }
+
+        let players = self
+            .players
+            .iter()
+            .filter(|p| previous_values.players.get(p.name()) != values.players.get(p.name()))
+            .cloned()
+            .collect();
+        let removed_players = previous_values
+            .players
+            .keys()
+            .filter(|name| !values.players.contains_key(*name))
+            .cloned()
+            .collect();
+
+        let objects = self
+            .objects
+            .iter()
+            .filter(|o| {
+                let key = ObjectKey::from(*o);
+                previous_values.objects.get(&key) != values.objects.get(&key)
+            })
+            .cloned()
+            .collect();
+        let removed_objects = previous_values
+            .objects
+            .keys()
+            .filter(|key| !values.objects.contains_key(*key))
+            .cloned()
+            .collect();
+
+        let ground = self
+            .ground
+            .data
+            .iter()
+            .zip(&previous.ground.data)
+            .enumerate()
+            .filter(|(_, (cell, previous))| cell != previous)
+            .map(|(index, (cell, _))| GroundChange {
+                index,
+                cell: cell.clone(),
+            })
+            .collect();
+
+        Some(GameStateDelta {
+            paused: self.paused,
+            players,
+            removed_players,
+            stats: self.stats.clone(),
+            ground,
+            objects,
+            removed_objects,
+            projectiles: self.projectiles.clone(),
+        })
+    }
+}
+
+/// Entities are compared by what dashboards see of them.
+fn to_value(entity: &impl Serialize) -> Value {
+    serde_json::to_value(entity).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchInfo {
+    pub width: u32,
+    pub height: u32,
+    pub seed: Option<u64>,
+    pub firewall_pattern: FirewallPattern,
+    pub firewall_speed: u32,
+    pub firewall_damage: u32,
+    pub vision_radius: u32,
+    pub tick_duration_ms: u64,
+    pub player_health: u32,
+    pub damage_multiplier: f32,
+    pub scoring: MatchScoring,
+    pub player_items: Vec<Item>,
+    pub chest_items: Vec<Item>,
+}
+
+/// Points of the config, with the camelCase keys of the other messages.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchScoring {
+    pub victory: i32,
+    pub kill_player: i32,
+    pub break_wall: i32,
+    pub use_buff: i32,
+    pub loot_chest: i32,
+    pub death_multiplier: f32,
+    pub survive_10_ticks: i32,
+    pub step_onto_trap: i32,
+    pub get_killed_by_player: i32,
+    pub get_killed_by_firewall: i32,
+    pub get_killed_by_yourself: i32,
+}
+
+impl From<&ScoreConfig> for MatchScoring {
+    fn from(config: &ScoreConfig) -> Self {
+        Self {
+            victory: config.victory,
+            kill_player: config.kill_player,
+            break_wall: config.break_wall,
+            use_buff: config.use_buff,
+            loot_chest: config.loot_chest,
+            death_multiplier: config.death_multiplier,
+            survive_10_ticks: config.survive_10_ticks,
+            step_onto_trap: config.step_onto_trap,
+            get_killed_by_player: config.get_killed_by_player,
+            get_killed_by_firewall: config.get_killed_by_firewall,
+            get_killed_by_yourself: config.get_killed_by_yourself,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::entities::objects::{TrapObject, WallObject};
+use crate::game::entities::player::{
+    Action, DamageRecord, DamageSource, InventoryItem, MatchStats,
+};
+use crate::game::items::Item;
+use crate::types::{Direction, PlayerId, Position, TickDuration};
+
+/// Snapshot of the running match, written with the autosaves and on shutdown to resume it when
+/// the server starts again. In its own folder, which Docker can mount to keep it across
+/// redeployments.
+pub const SNAPSHOT_PATH: &str = "snapshots/snapshot.json";
+
+/// Complete state of a match, unlike `Save` which only keeps the scores.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub seed: u64,
+    /// Ticks since the start of the match.
+    pub tick: u64,
+    pub rng: ChaCha12Rng,
+    pub spawn_cells: Vec<Position>,
+    /// Index of the next map of the rotation.
+    #[serde(default)]
+    pub map_rotation: usize,
+    pub map: SnapshotMap,
+    pub players: HashMap<PlayerId, SnapshotPlayer>,
+}
+
+impl Snapshot {
+    /// Reads the snapshot left by the last run of the server, if any.
+    pub fn load() -> Option<Self> {
+        match Self::read(Path::new(SNAPSHOT_PATH)) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                log::error!("Failed to read snapshot, starting a new match. {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, std::io::Error> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(std::io::Error::from)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), std::io::Error> {
+        let data = serde_json::to_string(self)?;
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        // Renaming replaces the file at once, so a crash while writing can't corrupt it
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(&temp_path, path)
+    }
+
+    /// Removes the snapshot of a match that is over.
+    pub fn remove() {
+        if let Err(e) = fs::remove_file(SNAPSHOT_PATH)
+            && e.kind() != ErrorKind::NotFound
+        {
+            log::error!("Failed to remove snapshot file. {}", e);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotMap {
+    pub width: u32,
+    pub height: u32,
+    pub firewall: Vec<Position>,
+    pub vias: Vec<Position>,
+    pub objects: Vec<SnapshotObject>,
+    pub projectiles: Vec<SnapshotProjectile>,
+}
+
+/// Object of the map, including the contents of chests hidden from the clients.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "camelCase")]
+#[serde(rename_all_fields = "camelCase")]
+pub enum SnapshotObject {
+    Chest {
+        position: Position,
+        items: Vec<Item>,
+        opened_by: HashSet<PlayerId>,
+    },
+    Wall(WallObject),
+    Trap(TrapObject),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotProjectile {
+    pub owner: PlayerId,
+    pub name: String,
+    pub position: Position,
+    pub direction: Direction,
+    pub remaining_ticks: TickDuration,
+    pub speed: u32,
+    pub damage: u32,
+}
+
+/// Every field of a player except the ones tied to the running server, like its connection.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotPlayer {
+    pub name: String,
+    pub score: i32,
+    pub hp: u32,
+    pub shield: u32,
+    pub position: Position,
+    pub last_position: Option<Position>,
+    pub inventory: Vec<InventoryItem>,
+    pub remaining_haste_ticks: TickDuration,
+    pub remaining_damage_ticks: TickDuration,
+    pub action: Option<Action>,
+    pub alive_time: TickDuration,
+    pub total_kills: u32,
+    pub total_wins: u32,
+    pub total_opened_chests: u32,
+    pub total_segfaults: u32,
+    pub total_games: u32,
+    pub match_start_rank: Option<usize>,
+    #[serde(default)]
+    pub rank_change: i32,
+    pub ready: bool,
+    pub in_match: bool,
+    pub banned: bool,
+    pub damage_history: VecDeque<DamageRecord>,
+    pub killed_by: Option<DamageSource>,
+    pub match_stats: MatchStats,
+}
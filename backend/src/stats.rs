@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::save::{HISTORY_DIR, SAVE_PATH, Save, SavedPlayer};
+use crate::types::PlayerId;
+
+const DEFAULT_OUTPUT_DIR: &str = "stats";
+
+type StatGetter = fn(&SavedPlayer) -> i64;
+
+const STATS: [(&str, StatGetter); 6] = [
+    ("score", |p| p.score as i64),
+    ("kills", |p| p.kills as i64),
+    ("wins", |p| p.wins as i64),
+    ("chests", |p| p.chests as i64),
+    ("segfaults", |p| p.segfaults as i64),
+    ("games", |p| p.games as i64),
+];
+
+struct StatsArgs {
+    save: PathBuf,
+    history: PathBuf,
+    output: PathBuf,
+}
+
+impl StatsArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = Self {
+            save: PathBuf::from(SAVE_PATH),
+            history: PathBuf::from(HISTORY_DIR),
+            output: PathBuf::from(DEFAULT_OUTPUT_DIR),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let target = match arg.as_str() {
+                "--save" => &mut parsed.save,
+                "--history" => &mut parsed.history,
+                "--out" => &mut parsed.output,
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            };
+            *target = args
+                .next()
+                .map(PathBuf::from)
+                .ok_or_else(|| format!("Missing value for {}", arg))?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Entry point of the `stats` subcommand.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = StatsArgs::parse(args).map_err(|e| {
+        format!(
+            "{}\nUsage: stats [--save <save.json>] [--history <dir>] [--out <dir>]",
+            e
+        )
+    })?;
+
+    let save = Save::read(&args.save)?;
+    let history = read_history(&args.history)?;
+
+    print!("{}", rankings(&save));
+    print!("{}", rename_history(&history, &save));
+
+    fs::create_dir_all(&args.output)
+        .map_err(|e| format!("Failed to create {}: {}", args.output.display(), e))?;
+    for (stat, value) in STATS {
+        let path = args.output.join(format!("{}.csv", stat));
+        fs::write(&path, timeline(&history, &save, value))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        println!("Wrote {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Reads every `save_<timestamp>.json` of the history folder, sorted by timestamp. Files that
+/// can't be read are skipped with a warning.
+fn read_history(folder: &Path) -> Result<Vec<(u64, Save)>, String> {
+    let entries =
+        fs::read_dir(folder).map_err(|e| format!("Failed to read {}: {}", folder.display(), e))?;
+
+    let mut history = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(timestamp) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix("save_"))
+            .and_then(|stem| stem.parse().ok())
+        else {
+            continue;
+        };
+
+        // A bad file only leaves a gap in the timelines
+        match Save::read(&path) {
+            Ok(save) => history.push((timestamp, save)),
+            Err(e) => eprintln!("Skipping history file. {}", e),
+        }
+    }
+
+    history.sort_by_key(|(timestamp, _)| *timestamp);
+    Ok(history)
+}
+
+fn rankings(save: &Save) -> String {
+    let mut out = String::new();
+    for (stat, value) in STATS {
+        let mut players = save
+            .players
+            .values()
+            .filter(|p| value(p) != 0)
+            .collect::<Vec<_>>();
+        players.sort_by(|a, b| value(b).cmp(&value(a)).then_with(|| a.name.cmp(&b.name)));
+
+        let _ = writeln!(out, "\n{}:", stat);
+        for (rank, player) in players.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "\t{}. {} - {} {}",
+                rank + 1,
+                player.name,
+                value(player),
+                stat
+            );
+        }
+    }
+    out
+}
+
+fn rename_history(history: &[(u64, Save)], save: &Save) -> String {
+    let mut names: HashMap<&PlayerId, Vec<&String>> = HashMap::new();
+    let saves = history.iter().map(|(_, save)| save).chain([save]);
+    for snapshot in saves {
+        for (id, player) in &snapshot.players {
+            let team_names = names.entry(id).or_default();
+            if team_names.last() != Some(&&player.name) {
+                team_names.push(&player.name);
+            }
+        }
+    }
+
+    let mut renames = names
+        .into_values()
+        .filter(|names| names.len() > 1)
+        .map(|names| {
+            names
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        })
+        .collect::<Vec<_>>();
+    renames.sort();
+
+    let mut out = String::from("\nrenames:\n");
+    for line in renames {
+        let _ = writeln!(out, "\t{}", line);
+    }
+    out
+}
+
+/// One row per history snapshot and one column per team, labelled with the team's latest name.
+fn timeline(history: &[(u64, Save)], save: &Save, value: StatGetter) -> String {
+    let mut teams = save.players.iter().collect::<Vec<_>>();
+    teams.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+
+    let mut out = String::from("timestamp");
+    for (_, player) in &teams {
+        let _ = write!(out, ",{}", csv_field(&player.name));
+    }
+    out.push('\n');
+
+    for (timestamp, snapshot) in history {
+        let _ = write!(out, "{}", timestamp);
+        for (id, _) in &teams {
+            match snapshot.players.get(*id) {
+                Some(player) => {
+                    let _ = write!(out, ",{}", value(player));
+                }
+                None => out.push(','),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::CONFIG;
+use crate::game::Game;
+use crate::game::names::{clean_name, name_key, validate_name};
+use crate::save::{Save, SavedPlayer};
+use crate::stats::csv_field;
+use crate::types::PlayerId;
+
+pub const DEFAULT_TOKEN_SHEET: &str = "tokens.csv";
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TeamEntry {
+    Name(String),
+    Team { name: String },
+}
+
+/// Reads team names from a JSON array (of names or `{ "name": ... }` objects) or from a CSV
+/// file whose first column is the team name.
+pub fn read_team_list(path: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let names: Vec<String> = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str::<Vec<TeamEntry>>(&text)
+            .map_err(|e| format!("Invalid team list {}: {}", path.display(), e))?
+            .into_iter()
+            .map(|entry| match entry {
+                TeamEntry::Name(name) | TeamEntry::Team { name } => name,
+            })
+            .collect()
+    } else {
+        text.lines()
+            .map(first_csv_column)
+            .filter(|name| !name.is_empty())
+            .enumerate()
+            .filter(|(i, name)| !(*i == 0 && name.eq_ignore_ascii_case("name")))
+            .map(|(_, name)| name)
+            .collect()
+    };
+
+    Ok(names
+        .into_iter()
+        .map(|name| name.trim().to_owned())
+        .collect())
+}
+
+fn first_csv_column(line: &str) -> String {
+    let line = line.trim();
+    match line.strip_prefix('"') {
+        Some(quoted) => {
+            let mut name = String::new();
+            let mut chars = quoted.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        name.push('"');
+                    }
+                    '"' => break,
+                    c => name.push(c),
+                }
+            }
+            name
+        }
+        None => line.split(',').next().unwrap_or_default().to_owned(),
+    }
+}
+
+/// Creates every team of the list, or none of them if a name breaks the team name rules, is
+/// duplicated in the list or is already taken by an existing team.
+pub fn import_teams(
+    game: &mut Game,
+    names: Vec<String>,
+) -> Result<Vec<(String, PlayerId)>, String> {
+    let names = check_names(names, &game.get_save())?;
+
+    Ok(names
+        .into_iter()
+        .filter_map(|name| {
+            let id = game.create_player(name.clone())?;
+            Some((name, id))
+        })
+        .collect())
+}
+
+/// Same as `import_teams`, but adds the teams directly to a save while the server is stopped.
+pub fn import_teams_into_save(
+    save: &mut Save,
+    names: Vec<String>,
+) -> Result<Vec<(String, PlayerId)>, String> {
+    let names = check_names(names, save)?;
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let id = PlayerId::new();
+            save.players
+                .insert(id.clone(), SavedPlayer::new(name.clone()));
+            (name, id)
+        })
+        .collect())
+}
+
+/// Cleans the names like renames do, and refuses the whole list on the first invalid name.
+fn check_names(names: Vec<String>, save: &Save) -> Result<Vec<String>, String> {
+    let rules = CONFIG.read().unwrap().team_names.clone();
+    let names = names
+        .iter()
+        .map(|name| {
+            let cleaned = clean_name(name);
+            validate_name(&cleaned, &rules)
+                .map(|()| cleaned)
+                .map_err(|e| format!("Refusing to import, invalid team name \"{}\": {}", name, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut seen = save
+        .players
+        .values()
+        .map(|p| name_key(&p.name, rules.normalized_uniqueness))
+        .collect::<HashSet<_>>();
+    let duplicates = names
+        .iter()
+        .filter(|name| !seen.insert(name_key(name, rules.normalized_uniqueness)))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !duplicates.is_empty() {
+        return Err(format!(
+            "Refusing to import, duplicate team names: {}",
+            duplicates.join(", ")
+        ));
+    }
+
+    Ok(names)
+}
+
+pub fn write_token_sheet(path: &Path, teams: &[(String, PlayerId)]) -> Result<(), String> {
+    let mut sheet = String::from("name,token\n");
+    for (name, id) in teams {
+        sheet.push_str(&format!("{},{}\n", csv_field(name), csv_field(id)));
+    }
+
+    fs::write(path, sheet).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Entry point of the `import` subcommand, to be used while the server is stopped.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: import <teams.csv|teams.json> [--out <tokens.csv>]";
+    let (file, output) = match args {
+        [file] => (file, DEFAULT_TOKEN_SHEET),
+        [file, flag, output] if flag == "--out" => (file, output.as_str()),
+        _ => return Err(usage.to_owned()),
+    };
+
+    let names = read_team_list(Path::new(file))?;
+    // Loading a whole game would restart it, which writes the history and removes the snapshot
+    let mut save = Save::load();
+    let teams = import_teams_into_save(&mut save, names)?;
+    save.save();
+    write_token_sheet(Path::new(output), &teams)?;
+
+    println!(
+        "Imported {} teams, tokens written to {}",
+        teams.len(),
+        output
+    );
+    Ok(())
+}
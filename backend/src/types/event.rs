@@ -1,5 +1,9 @@
 use serde::Serialize;
 
+use crate::types::PlayerId;
+
+/// Events name the teams for the clients, and keep their ids to find the agents involved even
+/// after a rename. The ids are the tokens of the teams, so they are never sent.
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
@@ -8,29 +12,66 @@ pub enum Event {
         killer: String,
         victim: String,
         weapon: Option<String>,
+        #[serde(skip)]
+        killer_id: Option<PlayerId>,
+        #[serde(skip)]
+        victim_id: PlayerId,
     },
     Nuke {
         player: String,
+        #[serde(skip)]
+        player_id: PlayerId,
     },
     GameEnd {
         winner: Option<String>,
+        #[serde(skip)]
+        winner_id: Option<PlayerId>,
     },
 }
 
 impl Event {
-    pub fn new_kill(killer: String, victim: String, weapon: Option<String>) -> Self {
+    /// Kill by a team, or by the firewall without a killer.
+    pub fn new_kill(
+        killer: Option<(PlayerId, String)>,
+        victim: (PlayerId, String),
+        weapon: Option<String>,
+    ) -> Self {
+        let (killer_id, killer) = match killer {
+            Some((id, name)) => (Some(id), name),
+            None => (None, "FireWall".to_owned()),
+        };
         Self::Kill {
             killer,
-            victim,
+            victim: victim.1,
             weapon,
+            killer_id,
+            victim_id: victim.0,
         }
     }
 
-    pub fn new_nuke(player: String) -> Self {
-        Self::Nuke { player }
+    pub fn new_nuke(player_id: PlayerId, player: String) -> Self {
+        Self::Nuke { player, player_id }
+    }
+
+    pub fn new_game_end(winner: Option<(PlayerId, String)>) -> Self {
+        let (winner_id, winner) = winner.unzip();
+        Self::GameEnd { winner, winner_id }
     }
 
-    pub fn new_game_end(winner: Option<String>) -> Self {
-        Self::GameEnd { winner }
+    /// Whether every agent should know about this event, not only the teams involved.
+    pub fn is_public(&self) -> bool {
+        matches!(self, Event::Nuke { .. } | Event::GameEnd { .. })
+    }
+
+    pub fn involves(&self, id: &PlayerId) -> bool {
+        match self {
+            Event::Kill {
+                killer_id,
+                victim_id,
+                ..
+            } => killer_id.as_ref() == Some(id) || victim_id == id,
+            Event::Nuke { player_id, .. } => player_id == id,
+            Event::GameEnd { winner_id, .. } => winner_id.as_ref() == Some(id),
+        }
     }
 }
@@ -1,7 +1,16 @@
 use serde::Serialize;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ScoreboardTeam {
     pub name: String,
     pub score: i32,
+    pub kills: u32,
+    pub wins: u32,
+    pub chests: u32,
+    pub segfaults: u32,
+    pub games_played: u32,
+    pub rank: usize,
+    pub rank_change: i32,
+    pub connected: bool,
 }
@@ -1,8 +1,11 @@
 import type { ReactNode } from "react";
 import { createContext, use, useEffect, useRef, useState } from "react";
-import type { Event, GameState, Team } from "~/lib/types";
+import type { Event, GameState, GameStateDelta, Position, Team } from "~/lib/types";
 import useLocalStorage from "~/lib/useLocalStorage";
 
+const PROTOCOL_VERSION = 2;
+const CAPABILITIES = ["deltaState"];
+
 const GameContext = createContext<{
     scoreboard: Team[];
     gameState: GameState;
@@ -10,8 +13,15 @@ const GameContext = createContext<{
     broadcast?: string;
     name: string | undefined;
     changeName: (name: string) => void;
+    renameFeedback?: RenameFeedback;
+    lobby?: Lobby;
+    pauseReason?: string;
 } | null>(null);
 
+export type Lobby = { teams: string[]; minPlayers: number; countdown?: number };
+
+export type RenameFeedback = { status: "accepted" | "pending" | "rejected"; message: string };
+
 const WebsocketContext = createContext<{
     token: string | null;
     connect: (token: string | null) => void;
@@ -30,6 +40,9 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
     const [token, setToken] = useLocalStorage<string | null>("team-token", null);
     const [name, setName] = useState<string>();
     const [linkFailed, setLinkFailed] = useState<boolean>(false);
+    const [renameFeedback, setRenameFeedback] = useState<RenameFeedback>();
+    const [lobby, setLobby] = useState<Lobby>();
+    const [pauseReason, setPauseReason] = useState<string>();
 
     useEffect(() => {
         if (websocket.current && new URL(websocket.current.url).pathname === url) return;
@@ -43,6 +56,8 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
                     type: "link",
                     clientType: "dashboard",
                     teamId: token,
+                    protocolVersion: PROTOCOL_VERSION,
+                    capabilities: CAPABILITIES,
                 }),
             );
         });
@@ -59,10 +74,18 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
         ws.addEventListener("message", (message) => {
             const data = JSON.parse(message.data) as WebsocketMessage;
             switch (data.type) {
+                case "linkAccepted":
+                    break;
                 case "gameState":
+                    setLobby(undefined);
                     setGameState(data.state);
                     setScoreboard(data.scoreboard?.sort((a, b) => b.score - a.score) ?? []);
                     break;
+                case "gameStateDelta":
+                    setLobby(undefined);
+                    setGameState((prev) => prev && applyGameStateDelta(prev, data.delta));
+                    setScoreboard(data.scoreboard?.sort((a, b) => b.score - a.score) ?? []);
+                    break;
                 case "events":
                     setEvents((prev) => [...data.events.reverse(), ...prev].slice(0, 100));
                     if (data.events.some((e) => e.type === "nuke")) {
@@ -72,12 +95,44 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
                 case "broadcast":
                     setBroadcast(data.message);
                     break;
+                case "lobby":
+                    setLobby({ teams: data.teams, minPlayers: data.minPlayers });
+                    break;
+                case "gamePaused":
+                case "gameResumed":
+                    setGameState((prev) => prev && { ...prev, paused: data.type === "gamePaused" });
+                    setPauseReason(data.type === "gamePaused" ? (data.reason ?? undefined) : undefined);
+                    break;
+                case "shutdown":
+                    console.log("Server shutting down");
+                    break;
+                case "countdown":
+                    setLobby((prev) => prev && { ...prev, countdown: data.seconds });
+                    break;
                 case "nameConfirmation":
                     setName(data.name);
                     setLinkFailed(false);
+                    setRenameFeedback({ status: "accepted", message: "Nom d'équipe mis à jour !" });
                     break;
-                case "linkFailed":
-                    setLinkFailed(true);
+                case "renamePending":
+                    setRenameFeedback({
+                        status: "pending",
+                        message: `Le nom ${data.name} est en attente d'approbation par les organisateurs.`,
+                    });
+                    break;
+                case "error":
+                    switch (data.code) {
+                        case "missingToken":
+                        case "unknownToken":
+                        case "banned":
+                            setLinkFailed(true);
+                            break;
+                        case "invalidRename":
+                            setRenameFeedback({ status: "rejected", message: data.message });
+                            break;
+                        default:
+                            console.warn("WebSocket error message:", data);
+                    }
                     break;
                 default:
                     console.warn("Unknown WebSocket message:", data);
@@ -102,6 +157,8 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
                 type: "link",
                 clientType: "dashboard",
                 teamId: token,
+                protocolVersion: PROTOCOL_VERSION,
+                capabilities: CAPABILITIES,
             }),
         );
     };
@@ -137,6 +194,9 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
                               broadcast,
                               name,
                               changeName,
+                              renameFeedback,
+                              lobby,
+                              pauseReason,
                           }
                         : null
                 }
@@ -147,6 +207,40 @@ export default function WebSocketProvider(props: { children: ReactNode }) {
     );
 }
 
+function applyGameStateDelta(state: GameState, delta: GameStateDelta): GameState {
+    const samePosition = (a: Position) => (b: Position) => a.x === b.x && a.y === b.y;
+    // A trap can be under a wall, so objects are identified by their type and position
+    type ObjectKey = GameStateDelta["removedObjects"][number];
+    const sameObject = (a: ObjectKey) => (b: ObjectKey) =>
+        a.type === b.type && samePosition(a.position)(b.position);
+    const updatedPlayers = new Map(delta.players.map((p) => [p.name, p]));
+    const removedPlayers = new Set(delta.removedPlayers);
+    const players = state.players
+        .filter((p) => !removedPlayers.has(p.name))
+        .map((p) => updatedPlayers.get(p.name) ?? p);
+    const addedPlayers = delta.players.filter((p) => !players.some((q) => q.name === p.name));
+    const data = [...state.ground.data];
+    for (const { index, cell } of delta.ground) {
+        data[index] = cell;
+    }
+
+    return {
+        ...state,
+        paused: delta.paused,
+        players: [...players, ...addedPlayers],
+        stats: delta.stats,
+        ground: { ...state.ground, data },
+        objects: [
+            ...state.objects.filter(
+                (o) =>
+                    !delta.objects.some(sameObject(o)) && !delta.removedObjects.some(sameObject(o)),
+            ),
+            ...delta.objects,
+        ],
+        projectiles: delta.projectiles,
+    };
+}
+
 export function useServerData() {
     const websocket = use(GameContext);
     return (
@@ -157,6 +251,9 @@ export function useServerData() {
             broadcast: undefined,
             name: undefined,
             changeName: undefined,
+            renameFeedback: undefined,
+            lobby: undefined,
+            pauseReason: undefined,
         }
     );
 }
@@ -168,12 +265,28 @@ export function useWebSocket() {
 }
 
 type WebsocketMessage =
+    | { type: "linkAccepted"; protocolVersion: number; capabilities: string[] }
     | {
           type: "gameState";
           scoreboard?: Team[];
           state: GameState;
       }
+    | {
+          type: "gameStateDelta";
+          scoreboard?: Team[];
+          delta: GameStateDelta;
+      }
     | { type: "events"; events: Event[] }
     | { type: "nameConfirmation"; name: string }
-    | { type: "linkFailed" }
-    | { type: "broadcast"; message: string };
+    | { type: "renamePending"; name: string }
+    | {
+          type: "error";
+          code: "missingToken" | "unknownToken" | "banned" | "badJson" | "invalidRename" | "notLinked";
+          message: string;
+          context: unknown;
+      }
+    | { type: "broadcast"; message: string }
+    | { type: "lobby"; teams: string[]; minPlayers: number }
+    | { type: "countdown"; seconds: number }
+    | { type: "shutdown" }
+    | { type: "gamePaused" | "gameResumed"; reason: string | null };
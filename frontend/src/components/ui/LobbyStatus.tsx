@@ -0,0 +1,32 @@
+import { useServerData } from "../WebSocketProvider";
+
+export default function LobbyStatus() {
+    const { lobby } = useServerData();
+
+    if (!lobby) return null;
+
+    return (
+        <div className="-translate-x-1/2 pointer-events-none fixed bottom-4 left-1/2 z-30">
+            <div
+                className="rounded border-4 px-8 py-2 text-center font-bold shadow-xl"
+                style={{
+                    backgroundColor: "#EB661B",
+                    borderColor: "#F8EFE4",
+                    color: "#F8EFE4",
+                    fontFamily: "Chilada, sans-serif",
+                }}
+            >
+                {lobby.countdown !== undefined ? (
+                    <span className="block text-2xl">DÉBUT DANS {lobby.countdown}...</span>
+                ) : (
+                    <span className="block">
+                        EN ATTENTE DES ÉQUIPES ({lobby.teams.length}/{lobby.minPlayers})
+                    </span>
+                )}
+                {lobby.teams.length > 0 && (
+                    <span className="block font-normal text-sm">{lobby.teams.join(", ")}</span>
+                )}
+            </div>
+        </div>
+    );
+}
@@ -0,0 +1,24 @@
+import { useServerData } from "../WebSocketProvider";
+
+export default function PauseStatus() {
+    const { gameState, pauseReason } = useServerData();
+
+    if (!gameState?.paused) return null;
+
+    return (
+        <div className="-translate-x-1/2 -translate-y-1/2 pointer-events-none fixed top-1/2 left-1/2 z-30">
+            <div
+                className="rounded border-4 px-14 py-4 text-center font-bold shadow-xl"
+                style={{
+                    backgroundColor: "#EB661B",
+                    borderColor: "#F8EFE4",
+                    color: "#F8EFE4",
+                    fontFamily: "Chilada, sans-serif",
+                }}
+            >
+                <span className="block text-2xl">PARTIE EN PAUSE</span>
+                {pauseReason && <span className="block font-normal text-sm">{pauseReason}</span>}
+            </div>
+        </div>
+    );
+}
@@ -4,16 +4,13 @@ import { Input } from "~/components/ui/input";
 import { useServerData } from "~/components/WebSocketProvider";
 
 export default function ChangeTeamName() {
-    const { name, changeName } = useServerData();
+    const { name, changeName, renameFeedback } = useServerData();
     const [newName, setNewName] = useState("");
-    const [confirmation, setConfirmation] = useState<string>();
 
     const handleSubmit = () => {
         if (newName.trim().length < 3 || newName.trim().length > 24 || !changeName) return;
 
         changeName(newName.trim());
-        setConfirmation("Nom d'équipe mis à jour !");
-        setTimeout(() => setConfirmation(undefined), 3000);
         setNewName("");
     };
 
@@ -52,7 +49,11 @@ export default function ChangeTeamName() {
                 Changer le nom
             </Button>
 
-            {confirmation && <p className="mt-4 text-green-600">{confirmation}</p>}
+            {renameFeedback && (
+                <p className={`mt-4 ${renameFeedback.status === "rejected" ? "text-red-600" : "text-green-600"}`}>
+                    {renameFeedback.message}
+                </p>
+            )}
         </form>
     );
 }
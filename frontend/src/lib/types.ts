@@ -7,6 +7,14 @@ export type Team = {
     id: string;
     name: string;
     score: number;
+    kills: number;
+    wins: number;
+    chests: number;
+    segfaults: number;
+    gamesPlayed: number;
+    rank: number;
+    rankChange: number;
+    connected: boolean;
 };
 
 export type Player = {
@@ -45,6 +53,7 @@ export type Object =
 
 export type GameState = {
     timestamp: number;
+    paused: boolean;
     players: Player[];
     stats: {
         alive_count: number;
@@ -60,6 +69,17 @@ export type GameState = {
     projectiles: Projectile[];
 };
 
+export type GameStateDelta = {
+    paused: boolean;
+    players: Player[];
+    removedPlayers: string[];
+    stats: GameState["stats"];
+    ground: { index: number; cell: Cell }[];
+    objects: Object[];
+    removedObjects: { type: Object["type"]; position: Position }[];
+    projectiles: Projectile[];
+};
+
 export type Event =
     | { type: "kill"; killer: string; victim: string; weapon?: string }
     | { type: "nuke"; player: string }
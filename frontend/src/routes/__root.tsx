@@ -6,6 +6,8 @@ import logo from "~/assets/logo.png";
 import Navbar from "~/components/NavBar";
 import BroadcastHistory from "~/components/ui/BroadcastHistory";
 import BroadcastMessage from "~/components/ui/BroadcastMessage";
+import LobbyStatus from "~/components/ui/LobbyStatus";
+import PauseStatus from "~/components/ui/PauseStatus";
 import UnreadNotifier from "~/components/ui/UnreadNotifier";
 import WebSocketProvider from "~/components/WebSocketProvider";
 import WindowProvider from "~/components/WindowProvider";
@@ -107,6 +109,8 @@ function RootComponent() {
                             <div className="relative grow">
                                 <div className="absolute inset-0 grid overflow-hidden rounded-md bg-background">
                                     <BroadcastMessage />
+                                    <LobbyStatus />
+                                    <PauseStatus />
                                     <BroadcastHistory visible={showHistory} />
                                     <UnreadNotifier isVisible={showHistory} onNewMessage={handleNewMessage} />
 